@@ -0,0 +1,99 @@
+use crate::{ interval::Interval, ray::Ray, vec3::Point3 };
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        Self { x: Interval::empty(), y: Interval::empty(), z: Interval::empty() }
+    }
+
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        let mut res = Self { x, y, z };
+        res.pad_to_minimums();
+        res
+    }
+
+    pub fn from_points(a: Point3, b: Point3) -> Self {
+        Self::new(
+            Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            Interval::new(a.z().min(b.z()), a.z().max(b.z()))
+        )
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        Self {
+            x: Interval::enclosing(a.x, b.x),
+            y: Interval::enclosing(a.y, b.y),
+            z: Interval::enclosing(a.z, b.z),
+        }
+    }
+
+    pub fn axis_interval(&self, axis: usize) -> Interval {
+        match axis {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let dx = self.x.size().max(0.0);
+        let dy = self.y.size().max(0.0);
+        let dz = self.z.size().max(0.0);
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, ray: &Ray, mut ray_t: Interval) -> bool {
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let origins = [origin.x(), origin.y(), origin.z()];
+        let directions = [direction.x(), direction.y(), direction.z()];
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / directions[axis];
+
+            let t0 = (ax.min - origins[axis]) * adinv;
+            let t1 = (ax.max - origins[axis]) * adinv;
+
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            ray_t.min = ray_t.min.max(t0);
+            ray_t.max = ray_t.max.min(t1);
+
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Flat primitives would get a zero-thickness box that rays can slip through.
+    fn pad_to_minimums(&mut self) {
+        let delta = 0.0001;
+        if self.x.size() < delta {
+            self.x = self.x.expand(delta);
+        }
+        if self.y.size() < delta {
+            self.y = self.y.expand(delta);
+        }
+        if self.z.size() < delta {
+            self.z = self.z.expand(delta);
+        }
+    }
+}
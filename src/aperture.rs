@@ -0,0 +1,80 @@
+use std::{ path::Path, sync::Arc };
+
+use image::ImageResult;
+
+use crate::{ PI, random_f32, rng::Rng, vec3::Vec3 };
+
+/// Shape of the lens opening, which out of focus highlights take on. Samples lie
+/// within the unit circle, which `Camera::defocus_angle` then scales.
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    /// Regular polygon with a corner per blade, turned by `rotation` degrees.
+    Polygon {
+        blades: u32,
+        rotation: f32,
+    },
+    Image(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    pub fn sample(&self, rng: &mut Rng) -> Vec3 {
+        match self {
+            Aperture::Circle => Vec3::random_in_unit_disk(rng),
+            Aperture::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                let step = (2.0 * PI) / (blades as f32);
+                // Every blade's triangle from the centre has the same area.
+                let k = ((random_f32(rng) * (blades as f32)) as u32).min(blades - 1);
+                let angle = rotation.to_radians() + (k as f32) * step;
+                let a = Vec3::new(angle.cos(), angle.sin(), 0.0);
+                let b = Vec3::new((angle + step).cos(), (angle + step).sin(), 0.0);
+                let (mut s, mut t) = (random_f32(rng), random_f32(rng));
+                if s + t > 1.0 {
+                    (s, t) = (1.0 - s, 1.0 - t);
+                }
+                s * a + t * b
+            }
+            Aperture::Image(mask) => mask.sample(rng),
+        }
+    }
+}
+
+/// Grayscale image of the aperture, sampled in proportion to brightness. The image's
+/// longer side spans the unit circle's diameter.
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    cdf: Vec<f32>,
+}
+
+impl ApertureMask {
+    pub fn load(path: &Path) -> ImageResult<Self> {
+        let img = image::open(path)?.to_luma32f();
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let mut total = 0.0;
+        let mut cdf: Vec<f32> = img
+            .pixels()
+            .map(|px| {
+                total += px[0].max(0.0);
+                total
+            })
+            .collect();
+        // An all black mask would let no light through; fall back to the full image.
+        let n = cdf.len() as f32;
+        for (k, value) in cdf.iter_mut().enumerate() {
+            *value = if total > 0.0 { *value / total } else { ((k as f32) + 1.0) / n };
+        }
+        Ok(ApertureMask { width, height, cdf })
+    }
+
+    fn sample(&self, rng: &mut Rng) -> Vec3 {
+        let u = random_f32(rng);
+        let k = self.cdf.partition_point(|&c| c <= u).min(self.cdf.len() - 1);
+        let (i, j) = (k % self.width, k / self.width);
+        let scale = 2.0 / (self.width.max(self.height) as f32);
+        let x = ((i as f32) + random_f32(rng) - (self.width as f32) / 2.0) * scale;
+        let y = ((self.height as f32) / 2.0 - (j as f32) - random_f32(rng)) * scale;
+        Vec3::new(x, y, 0.0)
+    }
+}
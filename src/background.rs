@@ -0,0 +1,181 @@
+use std::{ path::Path, sync::Arc };
+
+use image::{ DynamicImage, ImageResult };
+
+use crate::{ PI, random_f32, ray::Ray, rng::Rng, sky::PhysicalSky, vec3::{ Color, Vec3 } };
+
+#[derive(Clone)]
+pub enum Background {
+    /// The white-to-blue gradient every scene used before backgrounds were configurable.
+    Sky,
+    Solid(Color),
+    Environment(Arc<EnvironmentMap>),
+    PhysicalSky(PhysicalSky),
+}
+
+impl Background {
+    pub fn color(&self, ray: &Ray) -> Color {
+        match self {
+            Background::Sky => {
+                let unit_direction = ray.direction().to_unit_vector();
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+            }
+            Background::Solid(color) => *color,
+            Background::Environment(map) => map.color(ray.direction()),
+            Background::PhysicalSky(sky) => sky.color(ray.direction()),
+        }
+    }
+
+    /// Whether the background is bright and uneven enough to be sampled as a light.
+    pub fn is_light(&self) -> bool {
+        match self {
+            Background::Environment(_) => true,
+            Background::PhysicalSky(sky) => sky.sun_elevation() > 0.0,
+            _ => false,
+        }
+    }
+
+    /// Solid angle density of `random` generating `direction`.
+    pub fn pdf_value(&self, direction: Vec3) -> f32 {
+        match self {
+            Background::Environment(map) => map.pdf_value(direction),
+            Background::PhysicalSky(sky) => sky.pdf_value(direction),
+            _ => 0.0,
+        }
+    }
+
+    pub fn random(&self, rng: &mut Rng) -> Vec3 {
+        match self {
+            Background::Environment(map) => map.random(rng),
+            Background::PhysicalSky(sky) => sky.random(rng),
+            _ => Vec3::new(0.0, 1.0, 0.0),
+        }
+    }
+}
+
+/// Equirectangular image of everything around the scene, such as a Radiance .hdr or
+/// an EXR. The centre of the image lies towards -z and the top row straight up.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    /// Degrees the map is turned about the vertical axis.
+    rotation: f32,
+    intensity: f32,
+    /// Cumulative distribution of the rows, then of the pixels within each row, by
+    /// luminance weighted with the solid angle each pixel covers.
+    row_cdf: Vec<f32>,
+    pixel_cdf: Vec<f32>,
+}
+
+impl EnvironmentMap {
+    pub fn load(path: &Path, rotation: f32, intensity: f32) -> ImageResult<Self> {
+        let img = image::open(path)?;
+        // Float images already hold linear radiance; 8-bit ones are gamma encoded.
+        let linear = matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+        let img = img.into_rgb32f();
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        let pixels = img
+            .pixels()
+            .map(|px| {
+                let color = Color::new(px[0], px[1], px[2]);
+                if linear { color } else { color * color }
+            })
+            .collect();
+        Ok(EnvironmentMap::new(width, height, pixels, rotation, intensity))
+    }
+
+    pub fn new(
+        width: usize,
+        height: usize,
+        pixels: Vec<Color>,
+        rotation: f32,
+        intensity: f32
+    ) -> Self {
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut pixel_cdf = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for (j, row) in pixels.chunks(width).enumerate() {
+            let sin_theta = ((((j as f32) + 0.5) * PI) / (height as f32)).sin();
+            let mut row_total = 0.0;
+            for px in row {
+                row_total += luminance(*px) * sin_theta;
+                pixel_cdf.push(row_total);
+            }
+            let row_start = pixel_cdf.len() - width;
+            normalize(&mut pixel_cdf[row_start..], row_total);
+            total += row_total;
+            row_cdf.push(total);
+        }
+        normalize(&mut row_cdf, total);
+        EnvironmentMap { width, height, pixels, rotation, intensity, row_cdf, pixel_cdf }
+    }
+
+    pub fn color(&self, direction: Vec3) -> Color {
+        let (u, v) = self.uv(direction);
+        let i = ((u * (self.width as f32)) as usize).min(self.width - 1);
+        let j = ((v * (self.height as f32)) as usize).min(self.height - 1);
+        self.intensity * self.pixels[j * self.width + i]
+    }
+
+    pub fn pdf_value(&self, direction: Vec3) -> f32 {
+        let (u, v) = self.uv(direction);
+        let i = ((u * (self.width as f32)) as usize).min(self.width - 1);
+        let j = ((v * (self.height as f32)) as usize).min(self.height - 1);
+        // Taken from x and z rather than `v`, which loses all precision at the poles.
+        let d = direction.to_unit_vector();
+        let sin_theta = (d.x() * d.x() + d.z() * d.z()).sqrt();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let p = cdf_step(&self.row_cdf, j) * cdf_step(self.row(j), i);
+        (p * ((self.width * self.height) as f32)) / (2.0 * PI * PI * sin_theta)
+    }
+
+    pub fn random(&self, rng: &mut Rng) -> Vec3 {
+        let j = sample_cdf(&self.row_cdf, random_f32(rng));
+        let i = sample_cdf(self.row(j), random_f32(rng));
+        let u = ((i as f32) + random_f32(rng)) / (self.width as f32);
+        let v = ((j as f32) + random_f32(rng)) / (self.height as f32);
+        self.direction(u, v)
+    }
+
+    fn row(&self, j: usize) -> &[f32] {
+        &self.pixel_cdf[j * self.width..(j + 1) * self.width]
+    }
+
+    fn uv(&self, direction: Vec3) -> (f32, f32) {
+        let d = direction.to_unit_vector();
+        let phi = d.x().atan2(-d.z()) - self.rotation.to_radians();
+        let u = (0.5 + phi / (2.0 * PI)).rem_euclid(1.0);
+        (u, d.y().clamp(-1.0, 1.0).acos() / PI)
+    }
+
+    fn direction(&self, u: f32, v: f32) -> Vec3 {
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation.to_radians();
+        let (sin_theta, cos_theta) = (v * PI).sin_cos();
+        Vec3::new(sin_theta * phi.sin(), cos_theta, -sin_theta * phi.cos())
+    }
+}
+
+fn luminance(c: Color) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+/// Scales a running sum to end at 1, or makes it uniform when everything is black.
+fn normalize(cdf: &mut [f32], total: f32) {
+    let n = cdf.len() as f32;
+    for (k, value) in cdf.iter_mut().enumerate() {
+        *value = if total > 0.0 { *value / total } else { ((k as f32) + 1.0) / n };
+    }
+}
+
+/// Probability of picking entry `k` of a normalised CDF.
+fn cdf_step(cdf: &[f32], k: usize) -> f32 {
+    cdf[k] - (if k == 0 { 0.0 } else { cdf[k - 1] })
+}
+
+fn sample_cdf(cdf: &[f32], u: f32) -> usize {
+    cdf.partition_point(|&c| c <= u).min(cdf.len() - 1)
+}
@@ -0,0 +1,7 @@
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(e) = rusttracer::cli::run_render(&args[1..]) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
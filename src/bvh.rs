@@ -0,0 +1,170 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{ HitRecord, Hittable, HittableList, MaterialIds },
+    interval::Interval,
+    ray::Ray,
+    rng::Rng,
+    vec3::{ Point3, Vec3 },
+};
+
+/// At most this many objects go in a leaf without trying a split.
+const MAX_LEAF_SIZE: usize = 2;
+const SAH_TRAVERSAL_COST: f32 = 0.125;
+
+pub enum BvhNode {
+    Leaf {
+        objects: Vec<Box<dyn Hittable>>,
+        bbox: Aabb,
+    },
+    Branch {
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+        bbox: Aabb,
+    },
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> Self {
+        Self::build(list.into_objects())
+    }
+
+    pub fn build(mut objects: Vec<Box<dyn Hittable>>) -> Self {
+        let bbox = objects
+            .iter()
+            .fold(Aabb::empty(), |acc, object| Aabb::surrounding(&acc, &object.bounding_box()));
+
+        if objects.len() <= MAX_LEAF_SIZE {
+            return BvhNode::Leaf { objects, bbox };
+        }
+
+        let Some((axis, split)) = Self::find_sah_split(&mut objects, &bbox) else {
+            return BvhNode::Leaf { objects, bbox };
+        };
+
+        Self::sort_by_axis(&mut objects, axis);
+        let right_objects = objects.split_off(split);
+
+        BvhNode::Branch {
+            left: Box::new(Self::build(objects)),
+            right: Box::new(Self::build(right_objects)),
+            bbox,
+        }
+    }
+
+    /// Sweeps each axis for the split with the lowest surface area heuristic cost.
+    /// Returns `None` when keeping everything in one leaf is cheaper.
+    fn find_sah_split(objects: &mut [Box<dyn Hittable>], bbox: &Aabb) -> Option<(usize, usize)> {
+        let n = objects.len();
+        let parent_area = bbox.surface_area();
+        if parent_area <= 0.0 {
+            return Some((bbox.longest_axis(), n / 2));
+        }
+
+        let mut best: Option<(usize, usize)> = None;
+        let mut best_cost = n as f32;
+        let mut right_areas = vec![0.0; n];
+
+        for axis in 0..3 {
+            Self::sort_by_axis(objects, axis);
+
+            let mut right_box = Aabb::empty();
+            for i in (1..n).rev() {
+                right_box = Aabb::surrounding(&right_box, &objects[i].bounding_box());
+                right_areas[i] = right_box.surface_area();
+            }
+
+            let mut left_box = Aabb::empty();
+            for i in 1..n {
+                left_box = Aabb::surrounding(&left_box, &objects[i - 1].bounding_box());
+                let left_count = i as f32;
+                let right_count = (n - i) as f32;
+                let cost =
+                    SAH_TRAVERSAL_COST +
+                    (left_box.surface_area() * left_count + right_areas[i] * right_count) /
+                        parent_area;
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = Some((axis, i));
+                }
+            }
+        }
+        best
+    }
+
+    fn sort_by_axis(objects: &mut [Box<dyn Hittable>], axis: usize) {
+        objects.sort_by(|a, b| {
+            let ca = a.bounding_box().axis_interval(axis);
+            let cb = b.bounding_box().axis_interval(axis);
+            (ca.min + ca.max).total_cmp(&(cb.min + cb.max))
+        });
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>> {
+        match self {
+            BvhNode::Leaf { objects, bbox } => {
+                if !bbox.hit(ray, ray_t) {
+                    return None;
+                }
+                let mut rec = None;
+                let mut closest_so_far = ray_t.max;
+                for object in objects {
+                    let interval = Interval::new(ray_t.min, closest_so_far);
+                    if let Some(r) = object.hit(ray, interval, rng) {
+                        closest_so_far = r.t;
+                        rec = Some(r);
+                    }
+                }
+                rec
+            }
+            BvhNode::Branch { left, right, bbox } => {
+                if !bbox.hit(ray, ray_t) {
+                    return None;
+                }
+                let hit_left = left.hit(ray, ray_t, rng);
+                let max = hit_left.as_ref().map_or(ray_t.max, |r| r.t);
+                let hit_right = right.hit(ray, Interval::new(ray_t.min, max), rng);
+                hit_right.or(hit_left)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            BvhNode::Leaf { bbox, .. } | BvhNode::Branch { bbox, .. } => *bbox,
+        }
+    }
+
+    /// Sums over every object the direction passes through, not just the closest,
+    /// since any of them could have produced it.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let ray = Ray::new(origin, direction);
+        if !self.bounding_box().hit(&ray, Interval::new(0.001, f32::INFINITY)) {
+            return 0.0;
+        }
+        match self {
+            BvhNode::Leaf { objects, .. } =>
+                objects
+                    .iter()
+                    .map(|object| object.pdf_value(origin, direction))
+                    .sum(),
+            BvhNode::Branch { left, right, .. } =>
+                left.pdf_value(origin, direction) + right.pdf_value(origin, direction),
+        }
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        match self {
+            BvhNode::Leaf { objects, .. } => {
+                for object in objects {
+                    object.assign_ids(object_id, material_ids);
+                }
+            }
+            BvhNode::Branch { left, right, .. } => {
+                left.assign_ids(object_id, material_ids);
+                right.assign_ids(object_id, material_ids);
+            }
+        }
+    }
+}
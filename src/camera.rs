@@ -1,52 +1,378 @@
-use std::f32::INFINITY;
+use std::ops::{ Add, Div };
 use rayon::prelude::*;
-use crate::vec3::cross;
-use crate::{ _degrees_to_radians, Size, random_f32 };
+use crate::vec3::{ cross, dot };
+use crate::{ _degrees_to_radians, PI, Size, random_f32 };
+use crate::rng::{ Rng, pixel_rng };
 use crate::{
-    hittable::{ Hittable, HittableList },
+    aperture::Aperture,
+    background::Background,
+    denoise,
+    hittable::{ HitRecord, Hittable, HittableList },
     interval::Interval,
+    medium::Atmosphere,
     vec3::{ Color, Point3, Vec3 },
     ray::Ray,
 };
 
-#[derive(Clone, Copy)]
+/// How the camera maps pixels to ray directions. Only `Perspective` has a lens, so
+/// the others ignore the defocus settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Pinhole or thin lens with `fov` the vertical field of view.
+    Perspective,
+    /// Parallel rays; the image covers what a perspective view shows at `focus_dist`.
+    Orthographic,
+    /// The full sphere around the camera, 360° across and 180° up and down.
+    Equirectangular,
+    /// Angle from the view axis proportional to the distance from the image centre,
+    /// with `fov` across the circle that fits the image.
+    FisheyeEquidistant,
+    /// Like `FisheyeEquidistant`, but spacing directions by solid angle.
+    FisheyeEquisolid,
+    /// Perspective up and down but wrapping round horizontally, so wide panoramas keep
+    /// their proportions. Pixels at the centre match `Perspective` with the same `fov`.
+    Cylindrical,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 6] = [
+        Projection::Perspective,
+        Projection::Orthographic,
+        Projection::Equirectangular,
+        Projection::FisheyeEquidistant,
+        Projection::FisheyeEquisolid,
+        Projection::Cylindrical,
+    ];
+
+    /// The projection after this one when cycling through all of them.
+    pub fn next(self) -> Projection {
+        let idx = Projection::ALL.iter().position(|&p| p == self).unwrap_or(0);
+        Projection::ALL[(idx + 1) % Projection::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Projection::Perspective => "Perspective",
+            Projection::Orthographic => "Orthographic",
+            Projection::Equirectangular => "Equirectangular",
+            Projection::FisheyeEquidistant => "FisheyeEquidistant",
+            Projection::FisheyeEquisolid => "FisheyeEquisolid",
+            Projection::Cylindrical => "Cylindrical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoLayout {
+    /// Left eye in the left half of the image, right eye in the right half.
+    SideBySide,
+    /// Left eye in the top half, right eye in the bottom half.
+    TopBottom,
+}
+
+/// Renders both eyes into one image, each in its own half of the buffers. With the
+/// equirectangular projection this gives omni-directional stereo, where the eyes
+/// turn with every direction and there is no convergence plane.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stereo {
+    pub layout: StereoLayout,
+    /// Distance between the eyes, in scene units.
+    pub interocular: f32,
+    /// Distance at which the eyes' images line up, so objects there appear at the
+    /// depth of the screen.
+    pub convergence: f32,
+}
+
+#[derive(Clone)]
 struct RayGenParams {
+    projection: Projection,
+    stereo: Option<Stereo>,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
     center: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    /// Vertical field of view in radians.
+    fov: f32,
+    /// Size of one eye's image. The second eye of an odd sized image gets one more
+    /// column or row than this.
+    width: f32,
+    height: f32,
+    image_width: usize,
+    image_height: usize,
+    focus_dist: f32,
     defocus_angle: f32,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    aperture: Aperture,
+    anamorphic_squeeze: f32,
+    shutter_open: f32,
+    shutter_close: f32,
 }
 
 impl RayGenParams {
-    fn get_ray(&self, x: u16, y: u16) -> Ray {
-        let offset = Vec3::new(random_f32() - 0.5, random_f32() - 0.5, 0.0);
-
-        let pixel_center =
-            self.pixel00_loc +
-            ((x as f32) + offset.x()) * self.pixel_delta_u +
-            ((y as f32) + offset.y()) * self.pixel_delta_v;
-        let ray_origin = if self.defocus_angle <= 0.0 {
-            self.center
+    /// `None` for pixels the projection leaves empty, such as outside a fisheye's circle.
+    fn get_ray(&self, x: u16, y: u16, rng: &mut Rng) -> Option<Ray> {
+        let (x, y) = (x as f32, y as f32);
+        let Some(stereo) = self.stereo else {
+            return self.get_eye_ray(x, y, Vec3::new(0.0, 0.0, 0.0), rng);
+        };
+        // The second eye takes the odd column or row, so its pixels sit half a pixel
+        // off the first eye's to stay centred.
+        let (eye_side, x, y) = match stereo.layout {
+            StereoLayout::SideBySide if x >= self.width => {
+                let odd = (self.image_width as f32) - 2.0 * self.width;
+                (1.0, x - self.width - odd / 2.0, y)
+            }
+            StereoLayout::TopBottom if y >= self.height => {
+                let odd = (self.image_height as f32) - 2.0 * self.height;
+                (1.0, x, y - self.height - odd / 2.0)
+            }
+            _ => (-1.0, x, y),
+        };
+        let half_interocular = (eye_side * stereo.interocular) / 2.0;
+        let eye = half_interocular * self.u;
+        if self.projection == Projection::Perspective {
+            return self.get_eye_ray(x, y, eye, rng);
+        }
+
+        let ray = self.get_eye_ray(x, y, Vec3::new(0.0, 0.0, 0.0), rng)?;
+        let direction = ray.direction();
+        if self.projection == Projection::Equirectangular {
+            // Each direction gets its own pair of eyes, level and facing it.
+            let right = cross(&direction, &self.v);
+            let length = right.length();
+            let eye = if length > 0.0 { right * (half_interocular / length) } else { right };
+            return Some(Ray::with_time(ray.origin() + eye, direction, ray.time()));
+        }
+
+        // Shift the eye but keep aiming at the same point on the convergence plane.
+        let forward = -dot(&direction, &self.w);
+        if forward <= 1e-3 {
+            return Some(Ray::with_time(ray.origin() + eye, direction, ray.time()));
+        }
+        let depth = -dot(&(ray.origin() - self.center), &self.w);
+        let target = ray.at((stereo.convergence - depth) / forward);
+        let origin = ray.origin() + eye;
+        Some(Ray::with_time(origin, target - origin, ray.time()))
+    }
+
+    /// `eye` moves a perspective camera sideways for stereo. Its axis stays parallel
+    /// and the image shifts instead, so the eyes' views meet at the convergence
+    /// distance while each keeps its focus plane `focus_dist` along its own axis.
+    fn get_eye_ray(&self, x: f32, y: f32, eye: Vec3, rng: &mut Rng) -> Option<Ray> {
+        let offset = Vec3::new(random_f32(rng) - 0.5, random_f32(rng) - 0.5, 0.0);
+
+        let (ray_origin, ray_direction) = match self.projection {
+            Projection::Perspective => {
+                let mut pixel_center = self.pixel_on_focus_plane(x, y, offset);
+                if let Some(stereo) = self.stereo {
+                    let shift = 1.0 - self.focus_dist / stereo.convergence;
+                    pixel_center = pixel_center + shift * eye;
+                }
+                let lens_center = self.center + eye;
+                let ray_origin = if self.defocus_angle <= 0.0 {
+                    lens_center
+                } else {
+                    let p = self.aperture.sample(rng);
+                    let x = p.x() / self.anamorphic_squeeze;
+                    lens_center + x * self.defocus_disk_u + p.y() * self.defocus_disk_v
+                };
+                (ray_origin, pixel_center - ray_origin)
+            }
+            Projection::Orthographic => {
+                let pixel_center = self.pixel_on_focus_plane(x, y, offset);
+                (pixel_center + self.focus_dist * self.w, -self.focus_dist * self.w)
+            }
+            _ => {
+                // Offset from the image centre in pixels, with y up.
+                let sx = x + 0.5 + offset.x() - self.width / 2.0;
+                let sy = self.height / 2.0 - (y + 0.5 + offset.y());
+                let local = self.panorama_direction(sx, sy)?;
+                (self.center, local.x() * self.u + local.y() * self.v - local.z() * self.w)
+            }
+        };
+        let time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + random_f32(rng) * (self.shutter_close - self.shutter_open)
         } else {
-            let p = Vec3::random_in_unit_disk();
-            self.center + p.x() * self.defocus_disk_u + p.y() * self.defocus_disk_v
+            self.shutter_open
         };
-        let ray_direction = pixel_center - ray_origin;
-        Ray::new(ray_origin, ray_direction)
+        Some(Ray::with_time(ray_origin, ray_direction, time))
+    }
+
+    fn pixel_on_focus_plane(&self, x: f32, y: f32, offset: Vec3) -> Point3 {
+        self.pixel00_loc +
+            (x + offset.x()) * self.pixel_delta_u +
+            (y + offset.y()) * self.pixel_delta_v
+    }
+
+    /// Direction through image position (`sx`, `sy`) for the projections that are not
+    /// a plane, as (right, up, forward) components.
+    fn panorama_direction(&self, sx: f32, sy: f32) -> Option<Vec3> {
+        match self.projection {
+            Projection::Equirectangular => {
+                let phi = (sx / self.width) * 2.0 * PI;
+                let elevation = (sy / self.height) * PI;
+                let (sin_e, cos_e) = elevation.sin_cos();
+                Some(Vec3::new(cos_e * phi.sin(), sin_e, cos_e * phi.cos()))
+            }
+            Projection::FisheyeEquidistant | Projection::FisheyeEquisolid => {
+                let distance = (sx * sx + sy * sy).sqrt();
+                let r = distance / (self.width.min(self.height) / 2.0);
+                if r > 1.0 {
+                    return None;
+                }
+                let half_fov = (self.fov / 2.0).min(PI);
+                let theta = if self.projection == Projection::FisheyeEquidistant {
+                    r * half_fov
+                } else {
+                    2.0 * (r * (half_fov / 2.0).sin()).asin()
+                };
+                if distance <= 0.0 {
+                    return Some(Vec3::new(0.0, 0.0, 1.0));
+                }
+                let (sin_t, cos_t) = theta.sin_cos();
+                Some(Vec3::new((sin_t * sx) / distance, (sin_t * sy) / distance, cos_t))
+            }
+            _ => {
+                // Cylindrical: the same angle per pixel as perspective has at its centre.
+                let pixel_angle = (2.0 * (self.fov / 2.0).tan()) / self.height;
+                let phi = sx * pixel_angle;
+                Some(Vec3::new(phi.sin(), sy * pixel_angle, phi.cos()))
+            }
+        }
     }
 }
 
+/// Pixels need this many samples before their variance estimate is trusted.
+const ADAPTIVE_MIN_SAMPLES: u32 = 16;
+
+/// Auxiliary outputs, recorded from the first hit of every camera ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aov {
+    Normal,
+    Depth,
+    Albedo,
+    ObjectId,
+    MaterialId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 5] = [
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::MaterialId,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+        }
+    }
+}
+
+/// What `render` writes to the display buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewMode {
+    Beauty,
+    /// Samples taken per pixel, white being every full resolution pass.
+    SampleCount,
+    Aov(Aov),
+}
+
+impl ViewMode {
+    /// The view after this one when cycling through all of them.
+    pub fn next(self) -> ViewMode {
+        match self {
+            ViewMode::Beauty => ViewMode::SampleCount,
+            ViewMode::SampleCount => ViewMode::Aov(Aov::ALL[0]),
+            ViewMode::Aov(aov) => {
+                let idx = Aov::ALL.iter().position(|&a| a == aov).unwrap_or(0);
+                Aov::ALL.get(idx + 1).map_or(ViewMode::Beauty, |&next| ViewMode::Aov(next))
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ViewMode::Beauty => "beauty",
+            ViewMode::SampleCount => "sample count",
+            ViewMode::Aov(aov) => aov.name(),
+        }
+    }
+}
+
+/// Attributes of the first surface a camera ray hits, which feed the AOVs and guide
+/// the denoiser. Rays escaping to the background get a zero normal, depth and ids,
+/// and the background colour as albedo.
+#[derive(Debug, Clone, Copy)]
+pub struct FirstHit {
+    pub normal: Vec3,
+    pub depth: f32,
+    pub albedo: Color,
+    pub object_id: u32,
+    pub material_id: u32,
+}
+
+impl FirstHit {
+    const ZERO: FirstHit = FirstHit {
+        normal: Vec3::new(0.0, 0.0, 0.0),
+        depth: 0.0,
+        albedo: Color::new(0.0, 0.0, 0.0),
+        object_id: 0,
+        material_id: 0,
+    };
+}
+
+/// Ids cannot be averaged, so sums keep the ids of `self`: a pixel's first sample.
+impl Add for FirstHit {
+    type Output = FirstHit;
+    fn add(self, other: Self) -> Self::Output {
+        FirstHit {
+            normal: self.normal + other.normal,
+            depth: self.depth + other.depth,
+            albedo: self.albedo + other.albedo,
+            ..self
+        }
+    }
+}
+
+impl Div<f32> for FirstHit {
+    type Output = FirstHit;
+    fn div(self, constant: f32) -> Self::Output {
+        FirstHit {
+            normal: self.normal / constant,
+            depth: self.depth / constant,
+            albedo: self.albedo / constant,
+            ..self
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Camera {
+    pub projection: Projection,
+    /// Both eyes side by side or one above the other, or `None` for a single view.
+    pub stereo: Option<Stereo>,
     pub fov: f32,
     pub defocus_angle: f32,
     pub focus_dist: f32,
+    /// Shape of the lens opening, and so of out of focus highlights.
+    pub aperture: Aperture,
+    /// How many times taller than wide the aperture is squeezed, like the oval bokeh
+    /// of an anamorphic lens. 1 leaves it as it is.
+    pub anamorphic_squeeze: f32,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
     lookfrom: Point3,
-    vup: Vec3,
     u: Vec3,
     v: Vec3,
     w: Vec3,
@@ -55,15 +381,34 @@ pub struct Camera {
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
-    sample_max: u16,
+    pub sample_max: u16,
     pub sample_current: u16,
     high_res_max_depth: u16,
     low_res_max_depth: u16,
     yaw: f32,
     pitch: f32,
-    sample_ratio: u16,
+    pub sample_ratio: u16,
     pub color_buffer: Vec<Color>,
+    /// Per pixel sum of squared sample luminances, for the variance estimate.
+    luminance_sq_buffer: Vec<f32>,
+    sample_counts: Vec<u32>,
+    first_hit_buffer: Vec<FirstHit>,
     full_res_count: u32,
+    /// Relative standard error below which a pixel stops being sampled. `None`
+    /// samples every pixel on every pass.
+    pub noise_threshold: Option<f32>,
+    active_pixels: usize,
+    pub view: ViewMode,
+    /// Filter the displayed beauty view once full-resolution passes start; the blocky
+    /// previews before that are shown unfiltered. Saved images always use the raw accumulation.
+    pub denoise: bool,
+    /// Same seed and settings give bit-identical images, whatever the thread count.
+    pub seed: u64,
+    /// Medium the camera, and with it the whole scene, sits in.
+    pub atmosphere: Option<Atmosphere>,
+    /// Times between which camera rays are sent. Moving objects blur over the interval.
+    pub shutter_open: f32,
+    pub shutter_close: f32,
 }
 
 pub enum Direction {
@@ -73,18 +418,79 @@ pub enum Direction {
     Backward,
 }
 
+/// The parts of the camera the viewer steers. The render worker gets these rather
+/// than a whole camera, so its accumulation buffers are never copied.
+#[derive(Debug, Clone, Copy)]
+pub struct ViewParams {
+    pub image_size: Size,
+    pub position: Point3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov: f32,
+    pub focus_dist: f32,
+    pub defocus_angle: f32,
+    pub projection: Projection,
+}
+
+impl ViewParams {
+    pub fn move_camera(&mut self, dir: Direction) {
+        let speed = 0.1;
+        let (u, _, w) = view_basis(self.yaw, self.pitch);
+        match dir {
+            Direction::Backward => {
+                self.position = self.position + speed * w;
+            }
+            Direction::Forward => {
+                self.position = self.position + -speed * w;
+            }
+            Direction::Left => {
+                self.position = self.position + -speed * u;
+            }
+            Direction::Right => {
+                self.position = self.position + speed * u;
+            }
+        }
+    }
+
+    pub fn rotate_camera(&mut self, dx: f32, dy: f32) {
+        let sensitivity = 0.3;
+        self.yaw += dx * sensitivity;
+        self.pitch = (self.pitch + dy * sensitivity).clamp(-89.0, 89.0);
+    }
+}
+
+/// Camera right, up and backward vectors for a view direction given in degrees.
+fn view_basis(yaw: f32, pitch: f32) -> (Vec3, Vec3, Vec3) {
+    let radius = yaw.to_radians();
+    let pitch = pitch.to_radians();
+
+    let front = Vec3::new(
+        radius.cos() * pitch.cos(),
+        pitch.sin(),
+        radius.sin() * pitch.cos()
+    ).to_unit_vector();
+
+    let w = -front;
+    let u = cross(&Vec3::new(0.0, 1.0, 0.0), &w).to_unit_vector();
+    let v = cross(&w, &u);
+    (u, v, w)
+}
+
 impl Camera {
     pub fn new(fov: f32, size: Size, sample_max: u16, sample_ratio: u16) -> Self {
         const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
 
         let mut res = Self {
+            projection: Projection::Perspective,
+            stereo: None,
             fov,
             defocus_angle: 0.0,
             focus_dist: 3.4,
+            aperture: Aperture::Circle,
+            anamorphic_squeeze: 1.0,
             defocus_disk_u: ZERO,
             defocus_disk_v: ZERO,
             lookfrom: ZERO,
-            vup: Vec3::new(0.0, 1.0, 0.0),
             u: Vec3::new(1.0, 0.0, 0.0),
             v: Vec3::new(0.0, 1.0, 0.0),
             w: Vec3::new(0.0, 0.0, 1.0),
@@ -101,7 +507,18 @@ impl Camera {
             pitch: 0.0,
             sample_ratio,
             color_buffer: vec![Color::new(0.0, 0.0, 0.0); size.area()],
+            luminance_sq_buffer: vec![0.0; size.area()],
+            sample_counts: vec![0; size.area()],
+            first_hit_buffer: vec![FirstHit::ZERO; size.area()],
             full_res_count: 0,
+            noise_threshold: None,
+            active_pixels: size.area(),
+            view: ViewMode::Beauty,
+            denoise: false,
+            seed: 0,
+            atmosphere: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         };
         res.update();
         res
@@ -110,43 +527,39 @@ impl Camera {
     pub fn resize(&mut self, size: Size) {
         self.image_size = size;
         self.color_buffer.resize(size.area(), Color::new(0.0, 0.0, 0.0));
+        self.luminance_sq_buffer.resize(size.area(), 0.0);
+        self.sample_counts.resize(size.area(), 0);
+        self.first_hit_buffer.resize(size.area(), FirstHit::ZERO);
         self.clear();
     }
 
     pub fn clear(&mut self) {
         self.color_buffer.fill(Color::new(0.0, 0.0, 0.0));
+        self.luminance_sq_buffer.fill(0.0);
+        self.sample_counts.fill(0);
+        self.first_hit_buffer.fill(FirstHit::ZERO);
         self.full_res_count = 0;
+        self.active_pixels = self.color_buffer.len();
         self.sample_current = 0;
         self.update();
     }
 
     pub fn update(&mut self) {
-        let radius = self.yaw.to_radians();
-        let pitch = self.pitch.to_radians();
-
-        let front = Vec3::new(
-            radius.cos() * pitch.cos(),
-            pitch.sin(),
-            radius.sin() * pitch.cos()
-        ).to_unit_vector();
-
-        self.w = -front;
-        self.u = cross(&self.vup, &self.w).to_unit_vector();
-        self.v = cross(&self.w, &self.u);
+        (self.u, self.v, self.w) = view_basis(self.yaw, self.pitch);
 
         self.lookfrom = self.center;
 
+        let eye_size = self.eye_size();
         let theta = _degrees_to_radians(self.fov);
         let h = (theta / 2.0).tan();
         let viewport_heigth = 2.0 * h * self.focus_dist;
-        let viewport_width =
-            (viewport_heigth * (self.image_size.w as f32)) / (self.image_size.h as f32);
+        let viewport_width = (viewport_heigth * (eye_size.w as f32)) / (eye_size.h as f32);
 
         let viewport_u = viewport_width * self.u;
         let viewport_v = viewport_heigth * -self.v;
 
-        self.pixel_delta_u = viewport_u / (self.image_size.w as f32);
-        self.pixel_delta_v = viewport_v / (self.image_size.h as f32);
+        self.pixel_delta_u = viewport_u / (eye_size.w as f32);
+        self.pixel_delta_v = viewport_v / (eye_size.h as f32);
 
         let viewport_upper_left =
             self.center - self.focus_dist * self.w - viewport_u / 2.0 - viewport_v / 2.0;
@@ -157,58 +570,112 @@ impl Camera {
         self.defocus_disk_v = self.v * defocus_radius;
     }
 
-    pub fn move_camera(&mut self, dir: Direction) {
-        let speed = 0.1;
-        match dir {
-            Direction::Backward => {
-                self.center = self.center + speed * self.w;
-            }
-            Direction::Forward => {
-                self.center = self.center + -speed * self.w;
-            }
-            Direction::Left => {
-                self.center = self.center + -speed * self.u;
-            }
-            Direction::Right => {
-                self.center = self.center + speed * self.u;
-            }
+    /// The part of the image each eye gets: all of it without stereo.
+    pub fn eye_size(&self) -> Size {
+        let Size { w, h } = self.image_size;
+        match self.stereo.map(|stereo| stereo.layout) {
+            None => self.image_size,
+            Some(StereoLayout::SideBySide) => Size { w: (w / 2).max(1), h },
+            Some(StereoLayout::TopBottom) => Size { w, h: (h / 2).max(1) },
         }
-        self.clear();
-        self.update();
     }
 
-    pub fn rotate_camera(&mut self, dx: f32, dy: f32) {
-        let sensitivity = 0.3;
-        self.yaw += dx * sensitivity;
-        self.pitch += dy * sensitivity;
+    pub fn position(&self) -> Point3 {
+        self.center
+    }
 
-        if self.pitch > 89.0 {
-            self.pitch = 89.0;
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn set_view(&mut self, position: Point3, yaw: f32, pitch: f32) {
+        self.center = position;
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-89.0, 89.0);
+        self.clear();
+    }
+
+    pub fn view_params(&self) -> ViewParams {
+        ViewParams {
+            image_size: self.image_size,
+            position: self.center,
+            yaw: self.yaw,
+            pitch: self.pitch,
+            fov: self.fov,
+            focus_dist: self.focus_dist,
+            defocus_angle: self.defocus_angle,
+            projection: self.projection,
         }
-        if self.pitch < -89.0 {
-            self.pitch = -89.0;
+    }
+
+    /// Takes on the viewer's view parameters and starts the image over.
+    pub fn set_view_params(&mut self, params: ViewParams) {
+        self.fov = params.fov;
+        self.focus_dist = params.focus_dist;
+        self.defocus_angle = params.defocus_angle;
+        self.projection = params.projection;
+        if params.image_size != self.image_size {
+            self.resize(params.image_size);
         }
+        self.set_view(params.position, params.yaw, params.pitch);
+    }
 
-        self.sample_current = 0;
-        self.clear();
-        self.update();
+    pub fn render(
+        &mut self,
+        world: &HittableList,
+        lights: &HittableList,
+        background: &Background,
+        buffer: &mut Vec<u32>
+    ) {
+        self.render_cancellable(world, lights, background, buffer, &(|| false));
     }
 
-    pub fn render(&mut self, world: &HittableList, buffer: &mut Vec<u32>) {
-        if self.sample_current < self.sample_max {
+    /// Like `render`, but gives up on the pass as soon as `cancelled` returns true.
+    /// A cancelled pass leaves both the accumulation and `buffer` untouched and
+    /// returns false.
+    pub fn render_cancellable(
+        &mut self,
+        world: &HittableList,
+        lights: &HittableList,
+        background: &Background,
+        buffer: &mut Vec<u32>,
+        cancelled: &(dyn Fn() -> bool + Sync)
+    ) -> bool {
+        if !self.is_done() {
             let ratio = self.sample_ratio as usize;
             let block_size: usize = (ratio >> (self.sample_current as usize)).max(1);
 
+            let eye_size = self.eye_size();
             let params: RayGenParams = RayGenParams {
+                projection: self.projection,
+                stereo: self.stereo,
                 pixel00_loc: self.pixel00_loc,
                 pixel_delta_u: self.pixel_delta_u,
                 pixel_delta_v: self.pixel_delta_v,
                 center: self.center,
+                u: self.u,
+                v: self.v,
+                w: self.w,
+                fov: _degrees_to_radians(self.fov),
+                width: eye_size.w as f32,
+                height: eye_size.h as f32,
+                image_width: self.image_size.w,
+                image_height: self.image_size.h,
+                focus_dist: self.focus_dist,
                 defocus_angle: self.defocus_angle,
                 defocus_disk_u: self.defocus_disk_u,
                 defocus_disk_v: self.defocus_disk_v,
+                aperture: self.aperture.clone(),
+                anamorphic_squeeze: self.anamorphic_squeeze,
+                shutter_open: self.shutter_open,
+                shutter_close: self.shutter_close,
             };
             if block_size > 1 && self.full_res_count == 0 {
+                // Previews go straight to `buffer`; there are no first hits to guide a denoise.
                 let cols = (self.image_size.w + block_size - 1) / block_size;
                 let rows = (self.image_size.h + block_size - 1) / block_size;
                 let total_blocks = cols * rows;
@@ -218,21 +685,34 @@ impl Camera {
                 let block_colors: Vec<u32> = (0..total_blocks)
                     .into_par_iter()
                     .map(|bi| {
+                        if cancelled() {
+                            return 0;
+                        }
                         let bx = bi % cols;
                         let by = bi / cols;
                         let px = (bx * block_size + block_size / 2).min(self.image_size.w - 1);
                         let py = (by * block_size + block_size / 2).min(self.image_size.h - 1);
 
-                        let ray = params.get_ray(px as u16, py as u16);
-                        let pixel_color = Self::ray_color(&ray, max_depth, world);
-
-                        let intensity = Interval::new(0.0, 0.999);
-                        let r = (255.99 * intensity.clamp(pixel_color.x().sqrt())) as u32;
-                        let g = (255.99 * intensity.clamp(pixel_color.y().sqrt())) as u32;
-                        let b = (255.99 * intensity.clamp(pixel_color.z().sqrt())) as u32;
-                        (r << 16) | (g << 8) | b
+                        let mut rng = pixel_rng(self.seed, bi, self.sample_current as u32);
+                        let Some(ray) = params.get_ray(px as u16, py as u16, &mut rng) else {
+                            return 0;
+                        };
+                        let pixel_color = Self::ray_color(
+                            &ray,
+                            max_depth,
+                            world,
+                            lights,
+                            background,
+                            self.atmosphere.as_ref(),
+                            None,
+                            &mut rng
+                        );
+                        pixel_color.to_u32()
                     })
                     .collect();
+                if cancelled() {
+                    return false;
+                }
 
                 for bi in 0..total_blocks {
                     let bx = bi % cols;
@@ -253,61 +733,405 @@ impl Camera {
             } else {
                 let max_depth = self.high_res_max_depth;
 
-                self.color_buffer
-                    .par_iter_mut()
-                    .enumerate()
-                    .for_each(|(i, pixel)| {
+                let pass: Vec<Option<(Color, FirstHit)>> = (0..self.color_buffer.len())
+                    .into_par_iter()
+                    .map(|i| {
+                        if cancelled() || self.is_converged(i) {
+                            return None;
+                        }
                         let x = i % self.image_size.w;
                         let y = i / self.image_size.w;
-                        let ray = params.get_ray(x as u16, y as u16);
-                        let pixel_color = Self::ray_color(&ray, max_depth, world);
-                        *pixel = *pixel + pixel_color;
+                        let mut rng = pixel_rng(self.seed, i, self.sample_current as u32);
+                        let mut first_hit = FirstHit::ZERO;
+                        let Some(ray) = params.get_ray(x as u16, y as u16, &mut rng) else {
+                            return Some((Color::new(0.0, 0.0, 0.0), first_hit));
+                        };
+                        let pixel_color = Self::ray_color(
+                            &ray,
+                            max_depth,
+                            world,
+                            lights,
+                            background,
+                            self.atmosphere.as_ref(),
+                            Some(&mut first_hit),
+                            &mut rng
+                        );
+                        Some((pixel_color, first_hit))
+                    })
+                    .collect();
+                if cancelled() {
+                    return false;
+                }
+
+                self.color_buffer
+                    .par_iter_mut()
+                    .zip(self.luminance_sq_buffer.par_iter_mut())
+                    .zip(self.sample_counts.par_iter_mut())
+                    .zip(self.first_hit_buffer.par_iter_mut())
+                    .zip(pass)
+                    .for_each(|((((pixel, luminance_sq), count), pixel_first_hit), sample)| {
+                        if let Some((pixel_color, first_hit)) = sample {
+                            *pixel = *pixel + pixel_color;
+                            *luminance_sq += pixel_color.luminance() * pixel_color.luminance();
+                            *pixel_first_hit = if *count == 0 {
+                                first_hit
+                            } else {
+                                *pixel_first_hit + first_hit
+                            };
+                            *count += 1;
+                        }
                     });
                 self.full_res_count += 1;
+                self.active_pixels = (0..self.color_buffer.len())
+                    .into_par_iter()
+                    .filter(|&i| !self.is_converged(i))
+                    .count();
                 self.sample_current += 1;
 
-                let inv_samples: f32 = 1.0 / (self.full_res_count as f32);
-                buffer
-                    .par_iter_mut()
-                    .enumerate()
-                    .for_each(|(i, pixel)| {
-                        let pixel_color = self.color_buffer[i] * inv_samples;
-                        let intensity = Interval::new(0.0, 0.999);
-                        let r = (255.99 * intensity.clamp(pixel_color.x().sqrt())) as u32;
-                        let g = (255.99 * intensity.clamp(pixel_color.y().sqrt())) as u32;
-                        let b = (255.99 * intensity.clamp(pixel_color.z().sqrt())) as u32;
-                        *pixel = (r << 16) | (g << 8) | b;
-                    });
+                self.display(buffer);
             }
         } else {
-            let inv_samples = 1.0 / (self.full_res_count as f32).max(1.0);
+            self.display(buffer);
+        }
+        true
+    }
+
+    /// True once every pass is rendered, or every pixel has converged in adaptive mode.
+    pub fn is_done(&self) -> bool {
+        self.sample_current >= self.sample_max ||
+            (self.full_res_count > 0 && self.active_pixels == 0)
+    }
+
+    /// Average radiance of pixel `i` over the samples it received. Low resolution
+    /// preview passes never reach `color_buffer`, so they must not count here.
+    pub fn pixel_radiance(&self, i: usize) -> Color {
+        self.color_buffer[i] / (self.sample_counts[i] as f32).max(1.0)
+    }
+
+    /// First hit attributes of pixel `i`, averaged like `pixel_radiance`.
+    pub fn pixel_first_hit(&self, i: usize) -> FirstHit {
+        self.first_hit_buffer[i] / (self.sample_counts[i] as f32).max(1.0)
+    }
+
+    fn is_converged(&self, i: usize) -> bool {
+        let Some(threshold) = self.noise_threshold else {
+            return false;
+        };
+        let n = self.sample_counts[i];
+        if n < ADAPTIVE_MIN_SAMPLES {
+            return false;
+        }
+        let n = n as f32;
+        let mean = self.color_buffer[i].luminance() / n;
+        let variance_of_mean = (self.luminance_sq_buffer[i] / n - mean * mean).max(0.0) / (n - 1.0);
+        variance_of_mean.sqrt() <= threshold * mean.max(1e-3)
+    }
+
+    /// Redraws `buffer` from the accumulated passes in the current `view`.
+    pub fn display(&self, buffer: &mut Vec<u32>) {
+        if self.view == ViewMode::Beauty && self.denoise {
+            let pixels = 0..self.color_buffer.len();
+            let radiance: Vec<Color> = pixels
+                .clone()
+                .into_par_iter()
+                .map(|i| self.pixel_radiance(i))
+                .collect();
+            let guides: Vec<FirstHit> = pixels
+                .into_par_iter()
+                .map(|i| self.pixel_first_hit(i))
+                .collect();
             buffer
                 .par_iter_mut()
-                .enumerate()
-                .for_each(|(i, pixel)| {
-                    let pixel_color = self.color_buffer[i] * inv_samples;
-                    let intensity = Interval::new(0.0, 0.999);
-                    let r = (255.99 * intensity.clamp(pixel_color.x().sqrt())) as u32;
-                    let g = (255.99 * intensity.clamp(pixel_color.y().sqrt())) as u32;
-                    let b = (255.99 * intensity.clamp(pixel_color.z().sqrt())) as u32;
-                    *pixel = (r << 16) | (g << 8) | b;
+                .zip(denoise::atrous(&radiance, &guides, self.image_size, self.eye_size()))
+                .for_each(|(pixel, color)| {
+                    *pixel = color.to_u32();
                 });
+            return;
+        }
+
+        if let ViewMode::Aov(aov) = self.view {
+            buffer.copy_from_slice(&self.aov_pixels(aov));
+            return;
+        }
+
+        let full_res_count = (self.full_res_count as f32).max(1.0);
+        buffer
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(i, pixel)| {
+                *pixel = match self.view {
+                    ViewMode::SampleCount => {
+                        let t = (self.sample_counts[i] as f32) / full_res_count;
+                        Color::new(t, t, t).to_u32()
+                    }
+                    _ => self.pixel_radiance(i).to_u32(),
+                };
+            });
+    }
+
+    /// Raw AOV data of pixel `i`: normals in [-1, 1], depth as the distance along the
+    /// camera ray, ids as floats.
+    pub fn aov_value(&self, aov: Aov, i: usize) -> Color {
+        let first_hit = self.pixel_first_hit(i);
+        match aov {
+            Aov::Normal => first_hit.normal,
+            Aov::Depth => Color::new(first_hit.depth, first_hit.depth, first_hit.depth),
+            Aov::Albedo => first_hit.albedo,
+            Aov::ObjectId => {
+                let id = first_hit.object_id as f32;
+                Color::new(id, id, id)
+            }
+            Aov::MaterialId => {
+                let id = first_hit.material_id as f32;
+                Color::new(id, id, id)
+            }
         }
     }
 
-    pub fn ray_color(ray: &Ray, max_depth: u16, world: &HittableList) -> Color {
-        if max_depth <= 0 {
+    /// An AOV as the viewer shows it: depth brightest up close, ids as flat colours.
+    pub fn aov_pixels(&self, aov: Aov) -> Vec<u32> {
+        let pixels = 0..self.color_buffer.len();
+        let max_depth = if aov == Aov::Depth {
+            pixels
+                .clone()
+                .into_par_iter()
+                .map(|i| self.pixel_first_hit(i).depth)
+                .reduce(|| 0.0, f32::max)
+        } else {
+            0.0
+        };
+        pixels
+            .into_par_iter()
+            .map(|i| {
+                let first_hit = self.pixel_first_hit(i);
+                match aov {
+                    Aov::Normal => {
+                        let n = first_hit.normal;
+                        (Color::new(n.x() + 1.0, n.y() + 1.0, n.z() + 1.0) * 0.5).pack_u32()
+                    }
+                    Aov::Depth => {
+                        let t = if first_hit.depth > 0.0 {
+                            1.0 - (0.9 * first_hit.depth) / max_depth
+                        } else {
+                            0.0
+                        };
+                        Color::new(t, t, t).pack_u32()
+                    }
+                    Aov::Albedo => first_hit.albedo.to_u32(),
+                    Aov::ObjectId => id_color(first_hit.object_id),
+                    Aov::MaterialId => id_color(first_hit.material_id),
+                }
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn ray_color(
+        ray: &Ray,
+        max_depth: u16,
+        world: &HittableList,
+        lights: &HittableList,
+        background: &Background,
+        atmosphere: Option<&Atmosphere>,
+        first_hit: Option<&mut FirstHit>,
+        rng: &mut Rng
+    ) -> Color {
+        Self::trace(ray, max_depth, world, lights, background, atmosphere, None, first_hit, rng)
+    }
+
+    /// `bsdf_pdf` is the density the previous bounce sampled `ray` with, or `None`
+    /// when that bounce was not light sampled (camera rays and delta lobes), in which
+    /// case emission found by `ray` gets the full weight.
+    #[allow(clippy::too_many_arguments)]
+    fn trace(
+        ray: &Ray,
+        depth: u16,
+        world: &HittableList,
+        lights: &HittableList,
+        background: &Background,
+        atmosphere: Option<&Atmosphere>,
+        bsdf_pdf: Option<f32>,
+        first_hit: Option<&mut FirstHit>,
+        rng: &mut Rng
+    ) -> Color {
+        if depth <= 0 {
             return Color::new(0.0, 0.0, 0.0);
         }
 
-        if let Some(rec) = world.hit(&ray, Interval::new(0.001, INFINITY)) {
-            let Some((scattered, attenuation)) = rec.mat.scatter(&ray, &rec) else {
-                return Color::new(0.0, 0.0, 0.0);
+        let mut rec = world.hit(ray, Interval::new(0.001, f32::INFINITY), rng);
+        if let Some(atmosphere) = atmosphere {
+            rec = atmosphere.interact(ray, rec, rng);
+        }
+        let Some(rec) = rec else {
+            let mut color = background.color(ray);
+            if let Some(first_hit) = first_hit {
+                first_hit.albedo = color;
+            }
+            if let Some(bsdf_pdf) = bsdf_pdf.filter(|_| background.is_light()) {
+                let light_pdf = background.pdf_value(ray.direction()) *
+                    Self::environment_weight(lights, background);
+                color = color * power_heuristic(bsdf_pdf, light_pdf);
+            }
+            return color;
+        };
+        if let Some(first_hit) = first_hit {
+            *first_hit = FirstHit {
+                normal: rec.normal,
+                depth: rec.t * ray.direction().length(),
+                albedo: rec.mat.albedo(&rec),
+                object_id: rec.object_id,
+                material_id: rec.material_id,
             };
-            return attenuation * Self::ray_color(&scattered, max_depth - 1, world);
         }
-        let unit_direction = ray.direction().to_unit_vector();
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+
+        let mut emitted = rec.mat.emitted(ray, &rec);
+        if let Some(bsdf_pdf) = bsdf_pdf {
+            if emitted.length_squared() > 0.0 {
+                let light_pdf = Self::light_pdf(lights, background, ray.origin(), ray.direction());
+                emitted = emitted * power_heuristic(bsdf_pdf, light_pdf);
+            }
+        }
+
+        let Some((scattered, attenuation)) = rec.mat.scatter(ray, &rec, rng) else {
+            return emitted;
+        };
+
+        let scattered_pdf = if lights.is_empty() && !background.is_light() {
+            None
+        } else {
+            rec.mat.eval(ray, &rec, scattered.direction()).map(|(_, pdf)| pdf)
+        };
+        let direct = if scattered_pdf.is_some() {
+            Self::sample_lights(ray, &rec, world, lights, background, atmosphere, rng)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+
+        let indirect = Self::trace(
+            &scattered,
+            depth - 1,
+            world,
+            lights,
+            background,
+            atmosphere,
+            scattered_pdf,
+            None,
+            rng
+        );
+        emitted + direct + attenuation * indirect
+    }
+
+    /// Chance of picking the environment rather than one of `lights`.
+    fn environment_weight(lights: &HittableList, background: &Background) -> f32 {
+        match (lights.is_empty(), background.is_light()) {
+            (_, false) => 0.0,
+            (true, true) => 1.0,
+            (false, true) => 0.5,
+        }
+    }
+
+    fn light_pdf(
+        lights: &HittableList,
+        background: &Background,
+        origin: Point3,
+        direction: Vec3
+    ) -> f32 {
+        let weight = Self::environment_weight(lights, background);
+        let mut pdf = 0.0;
+        if weight > 0.0 {
+            pdf += weight * background.pdf_value(direction);
+        }
+        if weight < 1.0 {
+            pdf += (1.0 - weight) * lights.pdf_value(origin, direction);
+        }
+        pdf
+    }
+
+    /// Next event estimation: one shadow ray towards a light, MIS weighted against
+    /// the chance of the BSDF sample finding the same light.
+    #[allow(clippy::too_many_arguments)]
+    fn sample_lights(
+        ray: &Ray,
+        rec: &HitRecord,
+        world: &HittableList,
+        lights: &HittableList,
+        background: &Background,
+        atmosphere: Option<&Atmosphere>,
+        rng: &mut Rng
+    ) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let direction = if random_f32(rng) < Self::environment_weight(lights, background) {
+            background.random(rng)
+        } else {
+            lights.random(rec.p, rng)
+        };
+        let light_pdf = Self::light_pdf(lights, background, rec.p, direction);
+        if light_pdf <= 0.0 {
+            return black;
+        }
+        let Some((bsdf_cos, bsdf_pdf)) = rec.mat.eval(ray, rec, direction) else {
+            return black;
+        };
+        if bsdf_cos.length_squared() <= 0.0 {
+            return black;
+        }
+
+        let shadow_ray = Ray::with_time(rec.p, direction, ray.time());
+        let shadow_hit = world.hit(&shadow_ray, Interval::new(0.001, f32::INFINITY), rng);
+        let (mut light, distance) = match shadow_hit {
+            Some(light_rec) => {
+                let light = light_rec.mat.emitted(&shadow_ray, &light_rec);
+                (light, light_rec.t * direction.length())
+            }
+            None if background.is_light() => (background.color(&shadow_ray), f32::INFINITY),
+            None => {
+                return black;
+            }
+        };
+        if let Some(atmosphere) = atmosphere {
+            light = light * atmosphere.transmittance(distance);
+        }
+        bsdf_cos * light * (power_heuristic(light_pdf, bsdf_pdf) / light_pdf)
+    }
+}
+
+/// A well spread colour per id, with 0 (the background) black.
+fn id_color(id: u32) -> u32 {
+    if id == 0 {
+        return 0;
+    }
+    (id.wrapping_mul(0x9e37_79b1) >> 8) | 0x40_4040
+}
+
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    if a + b > 0.0 { a / (a + b) } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ Size, scene::Scene };
+
+    fn render_with_threads(threads: usize, seed: u64) -> Vec<[u32; 3]> {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let size = Size { w: 20, h: 12 };
+        let mut scene = Scene::create_scene1(size, seed);
+        let mut buffer = vec![0; size.area()];
+        pool.install(|| {
+            for _ in 0..5 {
+                scene.camera.render(&scene.world, &scene.lights, &scene.background, &mut buffer);
+            }
+        });
+        scene.camera.color_buffer
+            .iter()
+            .map(|c| [c.x().to_bits(), c.y().to_bits(), c.z().to_bits()])
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_is_bit_identical_across_thread_counts() {
+        assert_eq!(render_with_threads(1, 7), render_with_threads(4, 7));
+        assert_ne!(render_with_threads(1, 7), render_with_threads(1, 8));
     }
 }
@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::{ Size, save::{ self, ImageFormat }, scene::Scene };
+
+const USAGE: &str =
+    "usage: rusttracer-render [--scene N|FILE] [--width W] [--height H] [--spp S] [-o FILE]
+                         [--bit-depth 8|16] [--seed N] [--noise-threshold T] [--aovs]
+
+FILE extension selects the output format: .png, .exr (linear float) or .ppm
+--spp overrides the scene's own sample count
+--noise-threshold stops sampling pixels whose relative standard error falls below T
+--aovs adds normal, depth, albedo and id passes: EXR layers, or FILE.<pass>.png previews";
+
+pub enum SceneSource {
+    Builtin(usize),
+    File(PathBuf),
+}
+
+pub struct RenderArgs {
+    pub scene: SceneSource,
+    pub size: Size,
+    /// Samples per pixel, or `None` to keep the scene's `sample_max`.
+    pub spp: Option<u16>,
+    pub output: PathBuf,
+    pub format: ImageFormat,
+    pub seed: Option<u64>,
+    pub noise_threshold: Option<f32>,
+    pub aovs: bool,
+}
+
+impl Default for RenderArgs {
+    fn default() -> Self {
+        Self {
+            scene: SceneSource::Builtin(1),
+            size: Size { w: 500, h: 500 },
+            spp: None,
+            output: PathBuf::from("render.png"),
+            format: ImageFormat::Png8,
+            seed: None,
+            noise_threshold: None,
+            aovs: false,
+        }
+    }
+}
+
+impl RenderArgs {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut res = RenderArgs::default();
+        let mut iter = args.iter();
+        let mut bit_depth: u8 = 8;
+
+        while let Some(flag) = iter.next() {
+            let mut value = || iter.next().ok_or(format!("missing value for {}", flag));
+            match flag.as_str() {
+                "--scene" => {
+                    let value = value()?;
+                    res.scene = match value.parse() {
+                        Ok(idx) => SceneSource::Builtin(idx),
+                        Err(_) => SceneSource::File(PathBuf::from(value)),
+                    };
+                }
+                "--width" => {
+                    res.size.w = parse_number(flag, value()?)?;
+                }
+                "--height" => {
+                    res.size.h = parse_number(flag, value()?)?;
+                }
+                "--spp" => {
+                    res.spp = Some(parse_number(flag, value()?)?);
+                }
+                "-o" | "--output" => {
+                    res.output = PathBuf::from(value()?);
+                }
+                "--seed" => {
+                    res.seed = Some(parse_number(flag, value()?)?);
+                }
+                "--noise-threshold" => {
+                    res.noise_threshold = Some(parse_number(flag, value()?)?);
+                }
+                "--aovs" => {
+                    res.aovs = true;
+                }
+                "--bit-depth" => {
+                    bit_depth = parse_number(flag, value()?)?;
+                }
+                "-h" | "--help" => {
+                    return Err(USAGE.to_string());
+                }
+                _ => {
+                    return Err(format!("unknown argument {}\n{}", flag, USAGE));
+                }
+            }
+        }
+
+        if res.size.area() == 0 {
+            return Err("width and height must be greater than 0".to_string());
+        }
+        if res.spp == Some(0) {
+            return Err("spp must be greater than 0".to_string());
+        }
+        if res.noise_threshold.is_some_and(|t| t <= 0.0) {
+            return Err("noise threshold must be greater than 0".to_string());
+        }
+        res.format = match (ImageFormat::from_path(&res.output), bit_depth) {
+            (Err(e), _) => {
+                return Err(e.to_string());
+            }
+            (Ok(ImageFormat::Png8), 16) => ImageFormat::Png16,
+            (Ok(format), 8) => format,
+            (Ok(_), 16) => {
+                return Err("--bit-depth 16 is only supported for .png output".to_string());
+            }
+            (Ok(_), _) => {
+                return Err("--bit-depth must be 8 or 16".to_string());
+            }
+        };
+        Ok(res)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+fn create_scene(source: &SceneSource, size: Size, seed: u64) -> Result<Scene, String> {
+    match source {
+        SceneSource::Builtin(1) => Ok(Scene::create_scene1(size, seed)),
+        SceneSource::Builtin(2) => Ok(Scene::create_scene2(size, seed)),
+        SceneSource::Builtin(3) => Ok(Scene::create_scene3(size, seed)),
+        SceneSource::Builtin(idx) => Err(format!("unknown scene {}, expected 1, 2 or 3", idx)),
+        SceneSource::File(path) => Scene::from_file(path, size).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn run_render(args: &[String]) -> Result<(), String> {
+    let args = RenderArgs::parse(args)?;
+    let mut scene = create_scene(&args.scene, args.size, args.seed.unwrap_or(0))?;
+    if let Some(seed) = args.seed {
+        scene.camera.seed = seed;
+    }
+
+    // No one watches the preview, so every pass goes straight to full resolution.
+    scene.camera.sample_ratio = 1;
+    if let Some(spp) = args.spp {
+        scene.camera.sample_max = spp;
+    }
+    if args.noise_threshold.is_some() {
+        scene.camera.noise_threshold = args.noise_threshold;
+    }
+    scene.camera.clear();
+
+    let mut buffer = vec![0; args.size.area()];
+    let start = Instant::now();
+    while !scene.camera.is_done() {
+        scene.camera.render(
+            &scene.world,
+            &scene.lights,
+            &scene.background,
+            &mut buffer
+        );
+        let done = scene.camera.sample_current;
+        if done % 16 == 0 || scene.camera.is_done() {
+            println!("sample {}/{} ({:.1?})", done, scene.camera.sample_max, start.elapsed());
+        }
+    }
+
+    let written = if args.aovs {
+        save::write_image_with_aovs(&scene.camera, &args.output, args.format)
+    } else {
+        save::write_image(&scene.camera, &args.output, args.format)
+    };
+    written.map_err(|e| format!("failed to write {}: {}", args.output.display(), e))?;
+    println!("saved {}", args.output.display());
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use rayon::prelude::*;
+
+use crate::{ Size, camera::FirstHit, vec3::Color };
+
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+const ITERATIONS: u32 = 5;
+const SIGMA_COLOR: f32 = 4.0;
+const SIGMA_NORMAL: f32 = 0.3;
+const SIGMA_ALBEDO: f32 = 0.1;
+/// Relative to the centre pixel's depth, per unit of filter step.
+const SIGMA_DEPTH: f32 = 0.05;
+const MIN_ALBEDO: f32 = 1e-3;
+
+/// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010) guided by the per-pixel
+/// averages of the first hits. Lighting is divided by the albedo before filtering so
+/// textures stay sharp. `eye` is the size of the first stereo eye in the top left corner,
+/// or the whole image without stereo; taps never cross into the other eye.
+pub fn atrous(radiance: &[Color], guides: &[FirstHit], size: Size, eye: Size) -> Vec<Color> {
+    let mut illumination: Vec<Color> = radiance
+        .par_iter()
+        .zip(guides)
+        .map(|(radiance, guide)|
+            map_channels(*radiance, guide.albedo, |r, a| r / a.max(MIN_ALBEDO))
+        )
+        .collect();
+
+    for iteration in 0..ITERATIONS {
+        let step = 1 << iteration;
+        let sigma_color = SIGMA_COLOR / ((1 << iteration) as f32);
+        illumination = (0..illumination.len())
+            .into_par_iter()
+            .map(|i| filter_pixel(&illumination, guides, size, eye, i, step, sigma_color))
+            .collect();
+    }
+
+    illumination
+        .par_iter()
+        .zip(guides)
+        .map(|(illumination, guide)|
+            map_channels(*illumination, guide.albedo, |l, a| l * a.max(MIN_ALBEDO))
+        )
+        .collect()
+}
+
+fn filter_pixel(
+    illumination: &[Color],
+    guides: &[FirstHit],
+    size: Size,
+    eye: Size,
+    i: usize,
+    step: usize,
+    sigma_color: f32
+) -> Color {
+    let x = (i % size.w) as isize;
+    let y = (i / size.w) as isize;
+    let xs = eye_span(x, eye.w, size.w);
+    let ys = eye_span(y, eye.h, size.h);
+    let center = illumination[i];
+    let guide = guides[i];
+    let depth_scale = SIGMA_DEPTH * guide.depth.max(1e-3) * (step as f32);
+
+    let mut sum = Color::new(0.0, 0.0, 0.0);
+    let mut weight_sum = 0.0;
+    for (ky, ky_weight) in KERNEL.iter().enumerate() {
+        let qy = y + ((ky as isize) - 2) * (step as isize);
+        if !ys.contains(&qy) {
+            continue;
+        }
+        for (kx, kx_weight) in KERNEL.iter().enumerate() {
+            let qx = x + ((kx as isize) - 2) * (step as isize);
+            if !xs.contains(&qx) {
+                continue;
+            }
+            let q = (qy as usize) * size.w + (qx as usize);
+            let other = guides[q];
+
+            let color_dist = (illumination[q] - center).length_squared();
+            let normal_dist = (other.normal - guide.normal).length_squared();
+            let albedo_dist = (other.albedo - guide.albedo).length_squared();
+            let depth_dist = (other.depth - guide.depth).abs();
+            let weight =
+                ky_weight *
+                kx_weight *
+                (
+                    -color_dist / (sigma_color * sigma_color) -
+                    normal_dist / (SIGMA_NORMAL * SIGMA_NORMAL) -
+                    albedo_dist / (SIGMA_ALBEDO * SIGMA_ALBEDO) -
+                    depth_dist / depth_scale
+                ).exp();
+
+            sum = sum + illumination[q] * weight;
+            weight_sum += weight;
+        }
+    }
+    // The centre tap always has weight 3/8 squared, so this never divides by zero.
+    sum / weight_sum
+}
+
+/// Pixels along one axis that share an eye with `p`.
+fn eye_span(p: isize, eye: usize, size: usize) -> std::ops::Range<isize> {
+    let eye = eye as isize;
+    if p < eye { 0..eye } else { eye..(size as isize) }
+}
+
+fn map_channels(a: Color, b: Color, f: impl Fn(f32, f32) -> f32) -> Color {
+    Color::new(f(a.x(), b.x()), f(a.y(), b.y()), f(a.z(), b.z()))
+}
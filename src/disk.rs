@@ -0,0 +1,105 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{ HitRecord, Hittable, MaterialIds },
+    interval::Interval,
+    material::Material,
+    onb::Onb,
+    random_f32,
+    ray::Ray,
+    rng::Rng,
+    vec3::{ Point3, Vec3, dot },
+};
+
+pub struct Disk {
+    center: Point3,
+    radius: f32,
+    /// `w` is the normal, `u` and `v` span the disk and orient its UVs.
+    frame: Onb,
+    mat: Arc<dyn Material + Send + Sync>,
+    bbox: Aabb,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl Disk {
+    pub fn new(
+        center: Point3,
+        normal: Vec3,
+        radius: f32,
+        mat: Arc<dyn Material + Send + Sync>
+    ) -> Self {
+        let frame = Onb::new(normal);
+        let n = frame.w;
+        // Each axis only sees the disk's extent perpendicular to the normal.
+        let extent = Vec3::new(
+            radius * (1.0 - n.x() * n.x()).max(0.0).sqrt(),
+            radius * (1.0 - n.y() * n.y()).max(0.0).sqrt(),
+            radius * (1.0 - n.z() * n.z()).max(0.0).sqrt()
+        );
+        let bbox = Aabb::from_points(center - extent, center + extent);
+        Disk { center, radius, frame, mat, bbox, object_id: 0, material_id: 0 }
+    }
+
+    fn intersect(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let normal = self.frame.w;
+        let denom = dot(&normal, &ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = dot(&normal, &(self.center - ray.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let offset = p - self.center;
+        let distance_squared = offset.length_squared();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(p, t, ray, &self.mat, normal);
+        // u goes around the rim, v from the centre out to it.
+        let phi = dot(&offset, &self.frame.v).atan2(dot(&offset, &self.frame.u));
+        rec.u = (phi + PI) / (2.0 * PI);
+        rec.v = distance_squared.sqrt() / self.radius;
+        rec.object_id = self.object_id;
+        rec.material_id = self.material_id;
+        Some(rec)
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord<'_>> {
+        self.intersect(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let ray = Ray::new(origin, direction);
+        let Some(rec) = self.intersect(&ray, Interval::new(0.001, f32::INFINITY)) else {
+            return 0.0;
+        };
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (dot(&direction, &self.frame.w) / direction.length()).abs();
+        distance_squared / (cosine * PI * self.radius * self.radius)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let r = self.radius * random_f32(rng).sqrt();
+        let phi = 2.0 * PI * random_f32(rng);
+        let p = self.center + self.frame.transform(Vec3::new(r * phi.cos(), r * phi.sin(), 0.0));
+        p - origin
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.object_id = object_id;
+        self.material_id = material_ids.id(&self.mat);
+    }
+}
@@ -1,6 +1,16 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::{ interval::Interval, material::Material, ray::Ray, vec3::{ Point3, Vec3, dot } };
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    interval::Interval,
+    material::Material,
+    random_f32,
+    ray::Ray,
+    rng::Rng,
+    vec3::{ Point3, Vec3, dot },
+};
 
 #[derive(Clone)]
 pub struct HitRecord<'a> {
@@ -8,7 +18,11 @@ pub struct HitRecord<'a> {
     pub normal: Vec3,
     pub mat: &'a Arc<dyn Material + Send + Sync>,
     pub t: f32,
+    pub u: f32,
+    pub v: f32,
     pub front_face: bool,
+    pub object_id: u32,
+    pub material_id: u32,
 }
 
 impl<'a> HitRecord<'a> {
@@ -21,45 +35,183 @@ impl<'a> HitRecord<'a> {
     ) -> Self {
         let front_face = dot(&ray.direction(), &outward_normal) < 0.0;
         let normal = if front_face { outward_normal } else { -outward_normal };
-        Self { p, normal, t, mat, front_face }
+        Self { p, normal, t, u: 0.0, v: 0.0, mat, front_face, object_id: 0, material_id: 0 }
     }
 }
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>>;
+    fn bounding_box(&self) -> Aabb;
+
+    /// Solid angle density of `random` generating `direction` from `origin`.
+    /// Shapes that cannot be sampled as lights keep the default of 0.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f32 {
+        0.0
+    }
+
+    fn random(&self, _origin: Point3, _rng: &mut Rng) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    /// Stamps the ids reported in hit records for the object and material AOVs.
+    /// Aggregates pass `object_id` down so all their primitives count as one object.
+    fn assign_ids(&mut self, _object_id: u32, _material_ids: &mut MaterialIds) {}
+}
+
+/// Numbers materials in order of first appearance, so ids are stable between runs.
+#[derive(Default)]
+pub struct MaterialIds {
+    ids: HashMap<usize, u32>,
+}
+
+impl MaterialIds {
+    pub fn id(&mut self, mat: &Arc<dyn Material + Send + Sync>) -> u32 {
+        let next = (self.ids.len() as u32) + 1;
+        *self.ids.entry(Arc::as_ptr(mat) as *const () as usize).or_insert(next)
+    }
 }
 
 pub struct HittableList {
     objects: Vec<Box<dyn Hittable>>,
+    bbox: Aabb,
+    object_count: u32,
+    material_ids: MaterialIds,
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>> {
         let mut rec = None;
         let mut closest_so_far = ray_t.max;
 
         for object in &self.objects {
-            if let Some(r) = object.hit(&ray, Interval { min: ray_t.min, max: closest_so_far }) {
+            let interval = Interval { min: ray_t.min, max: closest_so_far };
+            if let Some(r) = object.hit(ray, interval, rng) {
                 closest_so_far = r.t;
                 rec = Some(r);
             }
         }
         rec
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / (self.objects.len() as f32);
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let idx = (random_f32(rng) * (self.objects.len() as f32)) as usize;
+        self.objects[idx.min(self.objects.len() - 1)].random(origin, rng)
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        for object in &mut self.objects {
+            object.assign_ids(object_id, material_ids);
+        }
+    }
+}
+
+/// Lets the same object sit in both the world and the lights.
+impl Hittable for Arc<dyn Hittable> {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, ray_t, rng)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        (**self).random(origin, rng)
+    }
+
+    /// Shared objects keep the ids they were given before they were shared.
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        if let Some(object) = Arc::get_mut(self) {
+            object.assign_ids(object_id, material_ids);
+        }
+    }
 }
 
 impl HittableList {
     pub fn new() -> Self {
         HittableList {
             objects: Vec::new(),
+            bbox: Aabb::empty(),
+            object_count: 0,
+            material_ids: MaterialIds::default(),
         }
     }
 
-    pub fn add(&mut self, object: Box<dyn Hittable>) {
+    /// Objects are numbered from 1 in the order they are added; 0 is the background.
+    pub fn add(&mut self, mut object: Box<dyn Hittable>) {
+        self.object_count += 1;
+        object.assign_ids(self.object_count, &mut self.material_ids);
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
 
+    /// Adds `object` and returns a handle to the same instance, so it can also be
+    /// sampled as a light. It is numbered first, since that cannot happen once shared.
+    pub fn add_shared(&mut self, mut object: Box<dyn Hittable>) -> Arc<dyn Hittable> {
+        self.object_count += 1;
+        object.assign_ids(self.object_count, &mut self.material_ids);
+        let object: Arc<dyn Hittable> = Arc::from(object);
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
+        self.objects.push(Box::new(Arc::clone(&object)));
+        object
+    }
+
+    /// Numbers the materials of geometry that is about to be shared between instances,
+    /// since instances cannot reach into it once it is behind an `Arc`.
+    pub fn assign_material_ids(&mut self, object: &mut dyn Hittable) {
+        object.assign_ids(0, &mut self.material_ids);
+    }
+
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::empty();
+        self.object_count = 0;
+        self.material_ids = MaterialIds::default();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.objects
+    }
+
+    /// Replaces the flat object list with a single BVH over the same objects, keeping
+    /// their ids. Unbounded objects such as planes would make every node infinite, so
+    /// they stay next to the BVH instead.
+    pub fn into_bvh(self) -> HittableList {
+        let (unbounded, bounded): (Vec<_>, Vec<_>) = self.objects
+            .into_iter()
+            .partition(|object| object.bounding_box().surface_area().is_infinite());
+        let mut objects = unbounded;
+        if !bounded.is_empty() {
+            objects.push(Box::new(BvhNode::build(bounded)));
+        }
+        HittableList {
+            objects,
+            bbox: self.bbox,
+            object_count: self.object_count,
+            material_ids: self.material_ids,
+        }
     }
 }
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{ HitRecord, Hittable, MaterialIds },
+    interval::Interval,
+    mat4::Mat4,
+    ray::Ray,
+    rng::Rng,
+    vec3::{ Point3, Vec3 },
+};
+
+/// Places shared geometry in the world with an affine transform. Rays are moved into
+/// the object's space rather than the object into the world, so any number of
+/// instances can point at the same geometry.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    to_world: Mat4,
+    to_object: Mat4,
+    /// Inverse transpose of `to_world`, which keeps normals perpendicular to the
+    /// surface under non-uniform scale.
+    normal_matrix: Mat4,
+    bbox: Aabb,
+    object_id: u32,
+}
+
+impl Instance {
+    /// Panics if `to_world` is not invertible.
+    pub fn new(object: Arc<dyn Hittable>, to_world: Mat4) -> Self {
+        let to_object = to_world.inverse().expect("instance transform must be invertible");
+        let bbox = transformed_bbox(&object.bounding_box(), &to_world);
+        Instance {
+            object,
+            to_world,
+            to_object,
+            normal_matrix: to_object.transpose(),
+            bbox,
+            object_id: 0,
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>> {
+        // The direction is not renormalised, so `t` means the same in both spaces.
+        let local = Ray::with_time(
+            self.to_object.transform_point(ray.origin()),
+            self.to_object.transform_vector(ray.direction()),
+            ray.time()
+        );
+        let mut rec = self.object.hit(&local, ray_t, rng)?;
+        rec.p = self.to_world.transform_point(rec.p);
+        rec.normal = self.normal_matrix.transform_vector(rec.normal).to_unit_vector();
+        rec.object_id = self.object_id;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let local = self.to_object.transform_vector(direction.to_unit_vector());
+        let pdf = self.object.pdf_value(self.to_object.transform_point(origin), local);
+        // Convert from object space solid angle: non-uniform scale stretches some
+        // directions more than others, by |det| / |local|^3 for a unit `direction`.
+        pdf * self.to_object.determinant().abs() / local.length().powi(3)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let local = self.object.random(self.to_object.transform_point(origin), rng);
+        self.to_world.transform_vector(local)
+    }
+
+    /// Shared geometry keeps the material ids it got before it was shared; the
+    /// instance only stamps its own object id.
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.object_id = object_id;
+        if let Some(object) = Arc::get_mut(&mut self.object) {
+            object.assign_ids(object_id, material_ids);
+        }
+    }
+}
+
+fn transformed_bbox(bbox: &Aabb, m: &Mat4) -> Aabb {
+    if bbox.surface_area().is_infinite() {
+        return Aabb::new(Interval::universe(), Interval::universe(), Interval::universe());
+    }
+    let mut res = Aabb::empty();
+    for corner in 0..8 {
+        let pick = |axis: Interval, bit: usize| {
+            if (corner & bit) == 0 { axis.min } else { axis.max }
+        };
+        let p = m.transform_point(Point3::new(pick(bbox.x, 1), pick(bbox.y, 2), pick(bbox.z, 4)));
+        res = Aabb::surrounding(&res, &Aabb::from_points(p, p));
+    }
+    res
+}
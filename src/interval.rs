@@ -1,5 +1,3 @@
-use std::f32::{ NEG_INFINITY, INFINITY };
-
 #[derive(Debug, Clone, Copy)]
 pub struct Interval {
     pub min: f32,
@@ -8,17 +6,21 @@ pub struct Interval {
 
 impl Interval {
     pub fn empty() -> Self {
-        Self { min: INFINITY, max: NEG_INFINITY }
+        Self { min: f32::INFINITY, max: f32::NEG_INFINITY }
     }
 
     pub fn universe() -> Self {
-        Self { min: NEG_INFINITY, max: INFINITY }
+        Self { min: f32::NEG_INFINITY, max: f32::INFINITY }
     }
 
     pub fn new(min: f32, max: f32) -> Self {
         Self { min, max }
     }
 
+    pub fn enclosing(a: Interval, b: Interval) -> Self {
+        Self { min: a.min.min(b.min), max: a.max.max(b.max) }
+    }
+
     pub fn size(&self) -> f32 {
         self.max - self.min
     }
@@ -34,4 +36,9 @@ impl Interval {
     pub fn clamp(&self, x: f32) -> f32 {
         x.clamp(self.min, self.max)
     }
+
+    pub fn expand(&self, delta: f32) -> Self {
+        let padding = delta / 2.0;
+        Self { min: self.min - padding, max: self.max + padding }
+    }
 }
@@ -0,0 +1,110 @@
+pub mod vec3;
+pub mod mat4;
+pub mod rng;
+pub mod onb;
+pub mod microfacet;
+pub mod aabb;
+pub mod bvh;
+pub mod ray;
+pub mod hittable;
+pub mod sphere;
+pub mod plane;
+pub mod quad;
+pub mod disk;
+pub mod triangle;
+pub mod mesh;
+pub mod instance;
+pub mod medium;
+pub mod interval;
+pub mod camera;
+pub mod aperture;
+pub mod material;
+pub mod texture;
+pub mod scene;
+#[cfg(feature = "gui")]
+pub mod ui;
+pub mod save;
+pub mod background;
+pub mod sky;
+pub mod cli;
+pub mod denoise;
+pub mod worker;
+
+use crate::rng::Rng;
+use rand::Rng as _;
+use crate::vec3::{ Color, Point3 };
+pub use std::f32::consts::PI;
+#[cfg(feature = "gui")]
+use std::ops::Add;
+
+fn _degrees_to_radians(degrees: f32) -> f32 {
+    (degrees * PI) / 180.0
+}
+
+pub fn random_f32(rng: &mut Rng) -> f32 {
+    rng.random()
+}
+
+pub fn random_f32_range(rng: &mut Rng, min: f32, max: f32) -> f32 {
+    rng.random_range(min..=max)
+}
+#[derive(Debug, Clone, Copy)]
+pub struct Size {
+    pub w: usize,
+    pub h: usize,
+}
+impl Size {
+    pub fn area(&self) -> usize {
+        self.h * self.w
+    }
+}
+
+impl PartialEq for Size {
+    fn eq(&self, other: &Self) -> bool {
+        self.w == other.w && self.h == other.h
+    }
+}
+
+#[cfg(feature = "gui")]
+pub struct WindowBuffer {
+    pub size: Size,
+    pub content: Vec<u32>,
+}
+
+#[cfg(feature = "gui")]
+impl WindowBuffer {
+    pub fn new(size: Size) -> Self {
+        WindowBuffer { size, content: vec![0; size.area()] }
+    }
+
+    pub fn resize(&mut self, new_size: Size) {
+        self.size = new_size;
+        self.content.resize(self.size.area(), 0);
+    }
+
+    fn get_coord2(&self, i: usize) -> Coord2 {
+        Coord2 { x: i % self.size.w, y: i / self.size.w }
+    }
+
+    fn get_index(&self, coord2: Coord2) -> usize {
+        coord2.y * self.size.w + coord2.x
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Copy)]
+pub struct Coord2 {
+    pub x: usize,
+    pub y: usize,
+}
+
+#[cfg(feature = "gui")]
+impl Add for Coord2 {
+    type Output = Coord2;
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
@@ -1,100 +1,50 @@
-pub mod vec3;
-pub mod ray;
-pub mod hittable;
-pub mod sphere;
-pub mod interval;
-pub mod camera;
-pub mod material;
-pub mod scene;
-pub mod ui;
-pub mod save;
-
-use crate::camera::Direction;
-use crate::ui::Ui;
-use crate::ui::text::TextString;
-use crate::vec3::{ Color, Point3 };
-pub use std::f32::{ INFINITY, NEG_INFINITY, consts::PI };
-use std::ops::Add;
+use rusttracer::background::Background;
+use rusttracer::camera::{ Direction, ViewMode, ViewParams };
+use rusttracer::ui::Ui;
+use rusttracer::ui::text::TextString;
+use rusttracer::vec3::Color;
+use rusttracer::{ Coord2, Size, WindowBuffer, cli, save };
+use std::path::Path;
 use std::time::Instant;
-use crate::scene::Scene;
+use rusttracer::scene::Scene;
+use rusttracer::worker::RenderWorker;
 use minifb::{ Key, Window, WindowOptions };
 
-fn _degrees_to_radians(degrees: f32) -> f32 {
-    (degrees * PI) / 180.0
-}
-
-pub fn random_f32() -> f32 {
-    rand::random()
-}
-
-pub fn random_f32_range(min: f32, max: f32) -> f32 {
-    rand::random_range(min..=max)
-}
-#[derive(Debug, Clone, Copy)]
-pub struct Size {
-    w: usize,
-    h: usize,
-}
-impl Size {
-    pub fn area(&self) -> usize {
-        self.h * self.w
-    }
-}
-
-impl PartialEq for Size {
-    fn eq(&self, other: &Self) -> bool {
-        self.w == other.w && self.h == other.h
-    }
-}
-
-pub struct WindowBuffer {
-    size: Size,
-    content: Vec<u32>,
-}
-
-impl WindowBuffer {
-    fn resize(&mut self, new_size: Size) {
-        self.size = new_size;
-        self.content.resize(self.size.area(), 0);
-    }
-
-    fn get_coord2(&self, i: usize) -> Coord2 {
-        Coord2 { x: i % self.size.w, y: i / self.size.w }
-    }
-
-    fn get_index(&self, coord2: Coord2) -> usize {
-        coord2.y * self.size.w + coord2.x
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct Coord2 {
-    x: usize,
-    y: usize,
-}
-
-impl Add for Coord2 {
-    type Output = Coord2;
-    fn add(self, other: Self) -> Self::Output {
-        Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("render") {
+        if let Err(e) = cli::run_render(&args[2..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
+        return;
     }
-}
 
-fn main() {
     let mut size = Size {
         w: 500,
         h: 500,
     };
     let mut scenes = vec![
-        Scene::create_scene1(size),
-        Scene::create_scene2(size),
-        Scene::create_scene3(size)
+        Some(Scene::create_scene1(size, 0)),
+        Some(Scene::create_scene2(size, 0)),
+        Some(Scene::create_scene3(size, 0))
     ];
+    for path in &args[1..] {
+        match Scene::from_file(Path::new(path), size) {
+            Ok(scene) => scenes.push(Some(scene)),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
     let scenes_len = scenes.len();
     let mut current_scene_idx = 0;
+    let (mut worker, mut camera, (mut view, mut denoise), mut background) = start_worker(
+        &mut scenes,
+        current_scene_idx,
+        size
+    );
 
     let mut window = Window::new("RustTracer", size.w, size.h, WindowOptions {
         resize: true,
@@ -106,18 +56,18 @@ fn main() {
     let mut show_ui = true;
 
     window.set_target_fps(200);
-    let mut window_buffer = WindowBuffer { size: size, content: vec![0; size.area()] };
+    let mut window_buffer = WindowBuffer::new(size);
     while window.is_open() && !window.is_key_down(Key::Escape) {
         let start: Instant = Instant::now();
 
+        let mut camera_changed = false;
         let (new_w, new_h) = window.get_size();
         let new_size = Size { h: new_h, w: new_w };
         if size != new_size {
             println!("resize");
             size = new_size;
-            for scene in &mut scenes {
-                scene.camera.resize(size);
-            }
+            camera.image_size = size;
+            camera_changed = true;
             window_buffer.resize(new_size);
             dbg!(window_buffer.size);
         }
@@ -134,50 +84,75 @@ fn main() {
             let min = 0.0001;
             if window.get_mouse_down(minifb::MouseButton::Left) {
                 if x_offset.abs() > min || y_offset.abs() > min {
-                    scenes[current_scene_idx].camera.rotate_camera(x_offset, y_offset);
+                    camera.rotate_camera(x_offset, y_offset);
+                    camera_changed = true;
                 }
             }
         }
-        let scene = &mut scenes[current_scene_idx];
-
         if window.is_key_down(Key::W) {
-            scene.camera.move_camera(Direction::Forward);
+            camera.move_camera(Direction::Forward);
+            camera_changed = true;
         }
         if window.is_key_down(Key::S) {
-            scene.camera.move_camera(Direction::Backward);
+            camera.move_camera(Direction::Backward);
+            camera_changed = true;
         }
         if window.is_key_down(Key::A) {
-            scene.camera.move_camera(Direction::Left);
+            camera.move_camera(Direction::Left);
+            camera_changed = true;
         }
         if window.is_key_down(Key::D) {
-            scene.camera.move_camera(Direction::Right);
+            camera.move_camera(Direction::Right);
+            camera_changed = true;
         }
         if window.is_key_pressed(Key::P, minifb::KeyRepeat::No) {
-            save::save_image(&scene.camera);
+            let scene = worker.stop();
+            let png16 = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+            save::save_image(&scene.camera, png16);
+            worker = RenderWorker::spawn(scene);
         }
         if window.is_key_down(Key::F) {
-            scene.camera.fov = scene.camera.fov + 1.0;
-            scene.camera.clear();
+            camera.fov = camera.fov + 1.0;
+            camera_changed = true;
         }
         if window.is_key_down(Key::V) {
-            scene.camera.fov = scene.camera.fov - 1.0;
-            scene.camera.clear();
+            camera.fov = camera.fov - 1.0;
+            camera_changed = true;
         }
         if window.is_key_down(Key::G) {
-            scene.camera.focus_dist = scene.camera.focus_dist + 0.1;
-            scene.camera.clear();
+            camera.focus_dist = camera.focus_dist + 0.1;
+            camera_changed = true;
         }
         if window.is_key_down(Key::B) {
-            scene.camera.focus_dist = scene.camera.focus_dist - 0.1;
-            scene.camera.clear();
+            camera.focus_dist = camera.focus_dist - 0.1;
+            camera_changed = true;
         }
         if window.is_key_down(Key::H) {
-            scene.camera.defocus_angle = scene.camera.defocus_angle - 0.1;
-            scene.camera.clear();
+            camera.defocus_angle = camera.defocus_angle - 0.1;
+            camera_changed = true;
         }
         if window.is_key_down(Key::N) {
-            scene.camera.defocus_angle = scene.camera.defocus_angle + 0.1;
-            scene.camera.clear();
+            camera.defocus_angle = camera.defocus_angle + 0.1;
+            camera_changed = true;
+        }
+        if let Background::PhysicalSky(sky) = &mut background {
+            let elevation = sky.sun_elevation();
+            let azimuth = sky.sun_azimuth();
+            let moved = if window.is_key_down(Key::Up) {
+                Some((elevation + 0.5, azimuth))
+            } else if window.is_key_down(Key::Down) {
+                Some((elevation - 0.5, azimuth))
+            } else if window.is_key_down(Key::Left) {
+                Some((elevation, azimuth - 1.0))
+            } else if window.is_key_down(Key::Right) {
+                Some((elevation, azimuth + 1.0))
+            } else {
+                None
+            };
+            if let Some((elevation, azimuth)) = moved {
+                sky.set_sun(elevation, azimuth);
+                worker.set_background(background.clone());
+            }
         }
         if window.is_key_pressed(Key::Space, minifb::KeyRepeat::No) {
             needs_scene_change = true;
@@ -185,12 +160,38 @@ fn main() {
         if window.is_key_pressed(Key::U, minifb::KeyRepeat::No) {
             show_ui = !show_ui;
         }
+        if window.is_key_pressed(Key::M, minifb::KeyRepeat::No) {
+            view = view.next();
+            worker.set_display(view, denoise);
+        }
+        if window.is_key_pressed(Key::O, minifb::KeyRepeat::No) {
+            camera.projection = camera.projection.next();
+            camera_changed = true;
+        }
+        if window.is_key_pressed(Key::K, minifb::KeyRepeat::No) {
+            denoise = !denoise;
+            worker.set_display(view, denoise);
+        }
         if needs_scene_change {
+            scenes[current_scene_idx] = Some(worker.stop());
             current_scene_idx = (current_scene_idx + 1) % scenes_len;
+            (worker, camera, (view, denoise), background) = start_worker(
+                &mut scenes,
+                current_scene_idx,
+                size
+            );
             needs_scene_change = false;
+        } else if camera_changed {
+            worker.set_view(camera);
         }
 
-        scene.camera.render(&scene.world, &mut window_buffer.content);
+        let sample_current = {
+            let frame = worker.frame();
+            if frame.size == size {
+                window_buffer.content.copy_from_slice(&frame.pixels);
+            }
+            frame.sample_current
+        };
 
         let elapsed_ms = start.elapsed().as_millis();
         let fps = if elapsed_ms > 0 { 1000 / (elapsed_ms as u128) } else { 0 };
@@ -198,7 +199,7 @@ fn main() {
         let black = Color::new(0.0, 0.0, 0.0);
         let ui_scale = ((size.w.min(size.h) as f32) / 600.0).max(1.0) as u16;
         let ui_opacity = 0.95;
-        let ui = Ui {
+        let mut ui = Ui {
             scale: ui_scale,
             inter_lines_height: 1,
             lines_content: vec![
@@ -215,7 +216,7 @@ fn main() {
                     opacity: ui_opacity,
                 },
                 TextString {
-                    content: format!("sample : {}", scene.camera.sample_current.to_string()),
+                    content: format!("sample : {}", sample_current.to_string()),
                     font_size: 3,
                     color: black,
                     opacity: ui_opacity,
@@ -233,19 +234,19 @@ fn main() {
                     opacity: ui_opacity,
                 },
                 TextString {
-                    content: format!("F/V: FOV {:.2}", scene.camera.fov),
+                    content: format!("F/V: FOV {:.2}", camera.fov),
                     font_size: 2,
                     color: black,
                     opacity: ui_opacity,
                 },
                 TextString {
-                    content: format!("G/B: Focus Distance {:.2}", scene.camera.focus_dist),
+                    content: format!("G/B: Focus Distance {:.2}", camera.focus_dist),
                     font_size: 2,
                     color: black,
                     opacity: ui_opacity,
                 },
                 TextString {
-                    content: format!("H/N: Defocus Angle {:.2}", scene.camera.defocus_angle),
+                    content: format!("H/N: Defocus Angle {:.2}", camera.defocus_angle),
                     font_size: 2,
                     color: black,
                     opacity: ui_opacity,
@@ -257,7 +258,28 @@ fn main() {
                     opacity: ui_opacity,
                 },
                 TextString {
-                    content: "P: save current image to file".to_string(),
+                    content: "P: save current image to file (Shift: 16-bit PNG)".to_string(),
+                    font_size: 2,
+                    color: black,
+                    opacity: ui_opacity,
+                },
+                TextString {
+                    content: format!("M: View {}", view.name()),
+                    font_size: 2,
+                    color: black,
+                    opacity: ui_opacity,
+                },
+                TextString {
+                    content: format!("O: Projection {}", camera.projection.name()),
+                    font_size: 2,
+                    color: black,
+                    opacity: ui_opacity,
+                },
+                TextString {
+                    content: format!(
+                        "K: Denoiser {} (full-res passes only)",
+                        if denoise { "on" } else { "off" }
+                    ),
                     font_size: 2,
                     color: black,
                     opacity: ui_opacity,
@@ -270,6 +292,18 @@ fn main() {
                 }
             ],
         };
+        if let Background::PhysicalSky(sky) = &background {
+            ui.lines_content.insert(8, TextString {
+                content: format!(
+                    "Arrows: Sun {:.1} up, {:.0} round",
+                    sky.sun_elevation(),
+                    sky.sun_azimuth()
+                ),
+                font_size: 2,
+                color: black,
+                opacity: ui_opacity,
+            });
+        }
         let top_left = Coord2 {
             x: 10,
             y: 10,
@@ -282,8 +316,26 @@ fn main() {
             String::from("RustTracer, fps :") +
             &fps.to_string() +
             &String::from(" sample :") +
-            &scene.camera.sample_current.to_string();
+            &sample_current.to_string();
         window.set_title(&title);
         window.update_with_buffer(&window_buffer.content, new_w, new_h).unwrap();
     }
+    worker.stop();
+}
+
+/// Hands scene `idx` to a new render worker, returning the viewer's copies of its
+/// view parameters, display settings and background.
+fn start_worker(
+    scenes: &mut [Option<Scene>],
+    idx: usize,
+    size: Size
+) -> (RenderWorker, ViewParams, (ViewMode, bool), Background) {
+    let mut scene = scenes[idx].take().expect("scene is already being rendered");
+    if scene.camera.image_size != size {
+        scene.camera.resize(size);
+    }
+    let params = scene.camera.view_params();
+    let display = (scene.camera.view, scene.camera.denoise);
+    let background = scene.background.clone();
+    (RenderWorker::spawn(scene), params, display, background)
 }
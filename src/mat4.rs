@@ -0,0 +1,148 @@
+use std::ops::Mul;
+
+use crate::vec3::{ Point3, Vec3 };
+
+/// Row-major 4x4 matrix for affine transforms. Points are column vectors, so
+/// `a * b` applies `b` first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4([[f32; 4]; 4]);
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub fn translation(offset: Vec3) -> Self {
+        Mat4([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Mat4([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Counter-clockwise rotation about `axis` when looking down it towards the origin.
+    pub fn rotation(axis: Vec3, degrees: f32) -> Self {
+        let a = axis.to_unit_vector();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        Mat4([
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y, 0.0],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x, 0.0],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Rotates about x, then y, then z, by the angles in degrees in `angles`.
+    pub fn rotation_euler(angles: Vec3) -> Self {
+        Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), angles.z()) *
+            Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), angles.y()) *
+            Mat4::rotation(Vec3::new(1.0, 0.0, 0.0), angles.x())
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        self.transform_vector(p) + Vec3::new(self.0[0][3], self.0[1][3], self.0[2][3])
+    }
+
+    /// Applies only the linear part, as for directions and offsets.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.0;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z()
+        )
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut res = [[0.0; 4]; 4];
+        for (i, row) in res.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.0[j][i];
+            }
+        }
+        Mat4(res)
+    }
+
+    /// Determinant of the linear part, i.e. how much the transform scales volumes.
+    pub fn determinant(&self) -> f32 {
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) -
+            m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0]) +
+            m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Inverse of an affine transform; the bottom row is assumed to be 0, 0, 0, 1.
+    /// Returns `None` when the transform squashes space flat.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let m = &self.0;
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+        let mut res = Mat4::IDENTITY;
+        res.0[0][0] = cofactor(1, 2, 1, 2) / det;
+        res.0[0][1] = -cofactor(0, 2, 1, 2) / det;
+        res.0[0][2] = cofactor(0, 1, 1, 2) / det;
+        res.0[1][0] = -cofactor(1, 2, 0, 2) / det;
+        res.0[1][1] = cofactor(0, 2, 0, 2) / det;
+        res.0[1][2] = -cofactor(0, 1, 0, 2) / det;
+        res.0[2][0] = cofactor(1, 2, 0, 1) / det;
+        res.0[2][1] = -cofactor(0, 2, 0, 1) / det;
+        res.0[2][2] = cofactor(0, 1, 0, 1) / det;
+
+        let offset = -res.transform_vector(Vec3::new(m[0][3], m[1][3], m[2][3]));
+        res.0[0][3] = offset.x();
+        res.0[1][3] = offset.y();
+        res.0[2][3] = offset.z();
+        Some(res)
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Self::Output {
+        let mut res = [[0.0; 4]; 4];
+        for (i, row) in res.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Mat4(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let m =
+            Mat4::translation(Vec3::new(1.0, -2.0, 3.0)) *
+            Mat4::rotation_euler(Vec3::new(30.0, 45.0, -60.0)) *
+            Mat4::scaling(Vec3::new(2.0, 0.5, 3.0));
+        let p = Point3::new(0.3, -1.7, 4.2);
+        let back = m.inverse().unwrap().transform_point(m.transform_point(p));
+        assert!((back - p).length() < 1e-5);
+        assert!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+}
@@ -1,37 +1,94 @@
-use crate::{ hittable::HitRecord, random_f32, ray::Ray, vec3::{ Color, Vec3, dot } };
+use std::f32::consts::PI;
+
+use crate::{
+    hittable::HitRecord,
+    microfacet::{ Ggx, fresnel_dielectric, fresnel_schlick, reflect, refract },
+    onb::Onb,
+    random_f32,
+    ray::Ray,
+    rng::Rng,
+    texture::SharedTexture,
+    vec3::{ Color, Vec3, dot },
+};
 
 pub trait Material {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)>;
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)>;
+
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// BSDF times cosine towards `direction`, with the pdf `scatter` samples it at.
+    /// `None` for delta lobes (mirrors, glass) that light sampling can never hit.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> Option<(Color, f32)> {
+        None
+    }
+
+    /// Surface colour at `rec`, used as a denoiser guide. White for materials without one.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
 }
 
 pub struct Lambertian {
-    pub albedo: Color,
+    pub albedo: SharedTexture,
+}
+
+impl Lambertian {
+    pub fn new(albedo: impl Into<SharedTexture>) -> Self {
+        Lambertian { albedo: albedo.into() }
+    }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
+        let mut scatter_direction = rec.normal + Vec3::random_unit_vector(rng);
         if scatter_direction.near_zero() {
             scatter_direction = rec.normal;
         }
-        Some((Ray::new(rec.p, scatter_direction), self.albedo))
+        let attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        Some((Ray::with_time(rec.p, scatter_direction, r_in.time()), attenuation))
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(Color, f32)> {
+        let cosine = dot(&rec.normal, &direction.to_unit_vector()).max(0.0);
+        let albedo = self.albedo.value(rec.u, rec.v, &rec.p);
+        Some((albedo * (cosine / PI), cosine / PI))
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
     }
 }
 
 pub struct Metal {
-    pub albedo: Color,
+    pub albedo: SharedTexture,
     pub fuzziness: f32,
 }
 
+impl Metal {
+    pub fn new(albedo: impl Into<SharedTexture>, fuzziness: f32) -> Self {
+        Metal { albedo: albedo.into(), fuzziness }
+    }
+}
+
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
         let mut reflected = r_in.direction().reflect(rec.normal);
-        reflected = reflected.to_unit_vector() + self.fuzziness * Vec3::random_unit_vector();
-        let scattered: Ray = Ray::new(rec.p, reflected);
+        reflected = reflected.to_unit_vector() + self.fuzziness * Vec3::random_unit_vector(rng);
+        let scattered: Ray = Ray::with_time(rec.p, reflected, r_in.time());
         if dot(&scattered.direction(), &rec.normal) <= 0.0 {
             return None;
         }
-        Some((scattered, self.albedo))
+        Some((scattered, self.albedo.value(rec.u, rec.v, &rec.p)))
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
     }
 }
 
@@ -41,7 +98,7 @@ pub struct Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
         let ri = if rec.front_face { 1.0 / self.refraction_index } else { self.refraction_index };
 
         let unit_direction = r_in.direction().to_unit_vector();
@@ -50,15 +107,15 @@ impl Material for Dielectric {
 
         let direction: Vec3;
 
-        if ri * sin_theta > 1.0 || self.reflectance(cos_theta) > random_f32() {
+        if ri * sin_theta > 1.0 || self.reflectance(cos_theta) > random_f32(rng) {
             direction = unit_direction.reflect(rec.normal);
         } else {
             direction = unit_direction.refract(rec.normal, ri);
         }
 
-        let direction = direction + self.frostedness * Vec3::random_unit_vector();
+        let direction = direction + self.frostedness * Vec3::random_unit_vector(rng);
 
-        let scattered: Ray = Ray::new(rec.p, direction);
+        let scattered: Ray = Ray::with_time(rec.p, direction, r_in.time());
 
         Some((scattered, Color::new(1.0, 1.0, 1.0)))
     }
@@ -71,3 +128,377 @@ impl Dielectric {
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
 }
+
+pub struct DiffuseLight {
+    pub emit: Color,
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _rng: &mut Rng) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emitted(&self, _r_in: &Ray, rec: &HitRecord) -> Color {
+        if rec.front_face { self.emit } else { Color::new(0.0, 0.0, 0.0) }
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+/// Phase function scattering equally in all directions, for smoke and fog.
+pub struct Isotropic {
+    pub albedo: SharedTexture,
+}
+
+impl Isotropic {
+    pub fn new(albedo: impl Into<SharedTexture>) -> Self {
+        Isotropic { albedo: albedo.into() }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
+        let scattered = Ray::with_time(rec.p, Vec3::random_unit_vector(rng), r_in.time());
+        Some((scattered, self.albedo.value(rec.u, rec.v, &rec.p)))
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, _direction: Vec3) -> Option<(Color, f32)> {
+        let albedo = self.albedo.value(rec.u, rec.v, &rec.p);
+        Some((albedo / (4.0 * PI), 1.0 / (4.0 * PI)))
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+/// Henyey-Greenstein phase function. `g` in (-1, 1) is the mean cosine of the
+/// scattering angle: positive scatters forward like haze, negative back towards the
+/// light, and 0 is isotropic.
+pub struct HenyeyGreenstein {
+    pub albedo: SharedTexture,
+    pub g: f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: impl Into<SharedTexture>, g: f32) -> Self {
+        HenyeyGreenstein { albedo: albedo.into(), g: g.clamp(-0.99, 0.99) }
+    }
+
+    fn phase(&self, cos_theta: f32) -> f32 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
+        let g = self.g;
+        let xi = random_f32(rng);
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_f32(rng);
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let direction = Onb::new(r_in.direction()).transform(local);
+        let scattered = Ray::with_time(rec.p, direction, r_in.time());
+        Some((scattered, self.albedo.value(rec.u, rec.v, &rec.p)))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(Color, f32)> {
+        let cos_theta = dot(&r_in.direction().to_unit_vector(), &direction.to_unit_vector());
+        let pdf = self.phase(cos_theta);
+        Some((self.albedo.value(rec.u, rec.v, &rec.p) * pdf, pdf))
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+/// Shading frame around the normal facing the incoming ray, and the direction back
+/// along that ray in it. `None` when interpolated normals leave the viewer below it.
+fn shading_frame(r_in: &Ray, rec: &HitRecord) -> Option<(Onb, Vec3)> {
+    let frame = Onb::new(rec.normal);
+    let wo = frame.to_local(-r_in.direction().to_unit_vector());
+    if wo.z() <= 0.0 { None } else { Some((frame, wo)) }
+}
+
+/// Samples through `eval`, so the weight and pdf of a scattered ray always match what
+/// light sampling computes for the same direction.
+fn scatter_by_eval(
+    material: &dyn Material,
+    r_in: &Ray,
+    rec: &HitRecord,
+    frame: &Onb,
+    wi: Vec3
+) -> Option<(Ray, Color)> {
+    let direction = frame.transform(wi);
+    let (value, pdf) = material.eval(r_in, rec, direction)?;
+    if pdf <= 0.0 {
+        return None;
+    }
+    Some((Ray::with_time(rec.p, direction, r_in.time()), value / pdf))
+}
+
+fn luminance_weight(c: Color) -> f32 {
+    c.luminance().max(0.0)
+}
+
+/// Rough metal: GGX microfacets with Schlick Fresnel starting from `albedo` at normal
+/// incidence. Unlike `Metal` it never reflects more light than it receives.
+pub struct Conductor {
+    pub albedo: SharedTexture,
+    pub roughness: f32,
+}
+
+impl Conductor {
+    pub fn new(albedo: impl Into<SharedTexture>, roughness: f32) -> Self {
+        Conductor { albedo: albedo.into(), roughness }
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
+        let (frame, wo) = shading_frame(r_in, rec)?;
+        let h = Ggx::new(self.roughness).sample_visible(wo, random_f32(rng), random_f32(rng));
+        scatter_by_eval(self, r_in, rec, &frame, reflect(wo, h))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(Color, f32)> {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let Some((frame, wo)) = shading_frame(r_in, rec) else {
+            return Some((black, 0.0));
+        };
+        let wi = frame.to_local(direction.to_unit_vector());
+        if wi.z() <= 0.0 {
+            return Some((black, 0.0));
+        }
+        let h = (wo + wi).to_unit_vector();
+        let ggx = Ggx::new(self.roughness);
+        let fresnel = fresnel_schlick(self.albedo.value(rec.u, rec.v, &rec.p), dot(&wo, &h));
+        let value = fresnel * ((ggx.d(h) * ggx.g2(wo, wi)) / (4.0 * wo.z()));
+        let pdf = ggx.visible_pdf(wo, h) / (4.0 * dot(&wo, &h));
+        Some((value, pdf))
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+/// Frosted glass: GGX microfacets that reflect or refract with the exact Fresnel
+/// term (Walter et al. 2007).
+pub struct RoughDielectric {
+    pub refraction_index: f32,
+    pub roughness: f32,
+}
+
+impl RoughDielectric {
+    /// Index on the far side of the shading normal over the near side.
+    fn eta(&self, rec: &HitRecord) -> f32 {
+        if rec.front_face { self.refraction_index } else { 1.0 / self.refraction_index }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
+        let (frame, wo) = shading_frame(r_in, rec)?;
+        let eta = self.eta(rec);
+        let h = Ggx::new(self.roughness).sample_visible(wo, random_f32(rng), random_f32(rng));
+        let reflectance = fresnel_dielectric(dot(&wo, &h), eta);
+        let wi = if random_f32(rng) < reflectance {
+            Some(reflect(wo, h)).filter(|wi| wi.z() > 0.0)
+        } else {
+            refract(wo, h, eta).filter(|wi| wi.z() < 0.0)
+        };
+        // A steep facet can send a ray to the wrong side, where `eval` would take a
+        // reflection for a refraction or the other way round.
+        scatter_by_eval(self, r_in, rec, &frame, wi?)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(Color, f32)> {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let Some((frame, wo)) = shading_frame(r_in, rec) else {
+            return Some((black, 0.0));
+        };
+        let wi = frame.to_local(direction.to_unit_vector());
+        if wi.z() == 0.0 {
+            return Some((black, 0.0));
+        }
+
+        let eta = self.eta(rec);
+        let is_reflection = wi.z() > 0.0;
+        let etap = if is_reflection { 1.0 } else { eta };
+        let mut h = wi * etap + wo;
+        if h.length_squared() <= 0.0 {
+            return Some((black, 0.0));
+        }
+        h = h.to_unit_vector();
+        if h.z() < 0.0 {
+            h = -h;
+        }
+        // Facets seen from behind by either direction cannot take part.
+        if dot(&h, &wi) * wi.z() < 0.0 || dot(&h, &wo) < 0.0 {
+            return Some((black, 0.0));
+        }
+
+        let ggx = Ggx::new(self.roughness);
+        let reflectance = fresnel_dielectric(dot(&wo, &h), eta);
+        let visible = ggx.visible_pdf(wo, h);
+        let (value, pdf) = if is_reflection {
+            let value = (reflectance * ggx.d(h) * ggx.g2(wo, wi)) / (4.0 * wo.z());
+            (value, (reflectance * visible) / (4.0 * dot(&wo, &h)))
+        } else {
+            let denom = dot(&wi, &h) + dot(&wo, &h) / etap;
+            let denom = denom * denom;
+            let jacobian = dot(&wi, &h).abs() / denom;
+            // Radiance is compressed into the smaller solid angle of the denser side.
+            let value =
+                ((1.0 - reflectance) * ggx.d(h) * ggx.g2(wo, wi) * jacobian * dot(&wo, &h)) /
+                (wo.z() * etap * etap);
+            (value, (1.0 - reflectance) * visible * jacobian)
+        };
+        Some((Color::new(value, value, value), pdf))
+    }
+}
+
+/// Layered material in the spirit of Disney's principled BSDF: a diffuse base, a GGX
+/// specular lobe whose colour goes from `specular` grey to `base_color` as `metallic`
+/// rises, and a clear varnish layer on top.
+pub struct Principled {
+    pub base_color: SharedTexture,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Reflectance at normal incidence for non-metals, scaled so 0.5 gives 4%.
+    pub specular: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+}
+
+/// Chance of sampling each lobe, given the view direction.
+struct LobeWeights {
+    diffuse: f32,
+    specular: f32,
+    clearcoat: f32,
+}
+
+impl Principled {
+    pub fn new(base_color: impl Into<SharedTexture>) -> Self {
+        Principled {
+            base_color: base_color.into(),
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+        }
+    }
+
+    fn specular_f0(&self, base: Color) -> Color {
+        let dielectric = 0.08 * self.specular.clamp(0.0, 1.0);
+        let dielectric = Color::new(dielectric, dielectric, dielectric);
+        dielectric * (1.0 - self.metallic) + base * self.metallic
+    }
+
+    /// The varnish reflects this much of the light coming straight back at `wo`.
+    fn clearcoat_weight(&self, wo: Vec3) -> f32 {
+        let f0 = Color::new(0.04, 0.04, 0.04);
+        self.clearcoat.clamp(0.0, 1.0) * fresnel_schlick(f0, wo.z()).x()
+    }
+
+    fn lobe_weights(&self, base: Color, wo: Vec3) -> LobeWeights {
+        let coat = self.clearcoat_weight(wo);
+        let specular = luminance_weight(fresnel_schlick(self.specular_f0(base), wo.z()));
+        // Every active lobe keeps a floor so highlights are never left to chance.
+        let floor = |active: bool, weight: f32| if active { weight.max(0.1) } else { 0.0 };
+        let diffuse = floor(
+            self.metallic < 1.0,
+            (1.0 - self.metallic) * (1.0 - coat) * (1.0 - specular) * luminance_weight(base)
+        );
+        let specular = floor(true, (1.0 - coat) * specular);
+        let clearcoat = floor(self.clearcoat > 0.0, coat);
+        let total = diffuse + specular + clearcoat;
+        LobeWeights {
+            diffuse: diffuse / total,
+            specular: specular / total,
+            clearcoat: clearcoat / total,
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
+        let (frame, wo) = shading_frame(r_in, rec)?;
+        let weights = self.lobe_weights(self.base_color.value(rec.u, rec.v, &rec.p), wo);
+        let lobe = random_f32(rng);
+        let wi = if lobe < weights.diffuse {
+            let normal = Vec3::new(0.0, 0.0, 1.0);
+            let direction = normal + Vec3::random_unit_vector(rng);
+            if direction.near_zero() { normal } else { direction.to_unit_vector() }
+        } else {
+            let roughness = if lobe < weights.diffuse + weights.specular {
+                self.roughness
+            } else {
+                self.clearcoat_roughness
+            };
+            let h = Ggx::new(roughness).sample_visible(wo, random_f32(rng), random_f32(rng));
+            reflect(wo, h)
+        };
+        scatter_by_eval(self, r_in, rec, &frame, wi)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(Color, f32)> {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let Some((frame, wo)) = shading_frame(r_in, rec) else {
+            return Some((black, 0.0));
+        };
+        let wi = frame.to_local(direction.to_unit_vector());
+        if wi.z() <= 0.0 {
+            return Some((black, 0.0));
+        }
+
+        let base = self.base_color.value(rec.u, rec.v, &rec.p);
+        let weights = self.lobe_weights(base, wo);
+        let h = (wo + wi).to_unit_vector();
+        let coat = self.clearcoat_weight(wo);
+        let f0 = self.specular_f0(base);
+
+        let diffuse_scale = (Color::new(1.0, 1.0, 1.0) - fresnel_schlick(f0, wo.z())) *
+            ((1.0 - self.metallic) * (1.0 - coat) * (wi.z() / PI));
+        let diffuse = base * diffuse_scale;
+
+        let ggx = Ggx::new(self.roughness);
+        let specular = fresnel_schlick(f0, dot(&wo, &h)) *
+            (((1.0 - coat) * ggx.d(h) * ggx.g2(wo, wi)) / (4.0 * wo.z()));
+        let specular_pdf = ggx.visible_pdf(wo, h) / (4.0 * dot(&wo, &h));
+
+        let coat_ggx = Ggx::new(self.clearcoat_roughness);
+        let coat_fresnel = fresnel_schlick(Color::new(0.04, 0.04, 0.04), dot(&wo, &h)).x();
+        let clearcoat =
+            (self.clearcoat.clamp(0.0, 1.0) *
+                coat_fresnel *
+                coat_ggx.d(h) *
+                coat_ggx.g2(wo, wi)) /
+            (4.0 * wo.z());
+        let clearcoat_pdf = coat_ggx.visible_pdf(wo, h) / (4.0 * dot(&wo, &h));
+
+        let value = diffuse + specular + Color::new(clearcoat, clearcoat, clearcoat);
+        let pdf =
+            weights.diffuse * (wi.z() / PI) +
+            weights.specular * specular_pdf +
+            weights.clearcoat * clearcoat_pdf;
+        Some((value, pdf))
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.base_color.value(rec.u, rec.v, &rec.p)
+    }
+}
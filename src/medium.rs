@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{ HitRecord, Hittable, MaterialIds },
+    interval::Interval,
+    material::Material,
+    random_f32,
+    ray::Ray,
+    rng::Rng,
+};
+
+/// Homogeneous volume filling a closed `boundary`, such as smoke in a box or the milky
+/// inside of a glass sphere. Rays scatter off `phase_function` after an exponentially
+/// distributed distance inside it, or pass through.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f32,
+    phase_function: Arc<dyn Material + Send + Sync>,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f32,
+        phase_function: Arc<dyn Material + Send + Sync>
+    ) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+            object_id: 0,
+            material_id: 0,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>> {
+        let entry = self.boundary.hit(ray, Interval::universe(), rng)?;
+        let exit = self.boundary.hit(ray, Interval::new(entry.t + 0.0001, f32::INFINITY), rng)?;
+        let t_entry = entry.t.max(ray_t.min).max(0.0);
+        let t_exit = exit.t.min(ray_t.max);
+        if t_entry >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction().length();
+        let distance_inside = (t_exit - t_entry) * ray_length;
+        let hit_distance = self.neg_inv_density * (1.0 - random_f32(rng)).ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_entry + hit_distance / ray_length;
+        let mut rec = scatter_record(ray, t, &self.phase_function);
+        rec.object_id = self.object_id;
+        rec.material_id = self.material_id;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.object_id = object_id;
+        self.material_id = material_ids.id(&self.phase_function);
+    }
+}
+
+/// Homogeneous medium the whole scene sits in, like haze or underwater murk. Besides
+/// scattering rays it dims the light that next event estimation finds.
+#[derive(Clone)]
+pub struct Atmosphere {
+    pub density: f32,
+    /// Distance the medium reaches along a ray that hits nothing. Past it the ray
+    /// escapes to the background, which would otherwise be hidden entirely.
+    pub extent: f32,
+    pub phase_function: Arc<dyn Material + Send + Sync>,
+}
+
+impl Atmosphere {
+    pub fn new(
+        density: f32,
+        extent: f32,
+        phase_function: Arc<dyn Material + Send + Sync>
+    ) -> Self {
+        Atmosphere { density, extent, phase_function }
+    }
+
+    /// Either scatters `ray` somewhere before it reaches `surface`, or lets it through.
+    pub fn interact<'a>(
+        &'a self,
+        ray: &Ray,
+        surface: Option<HitRecord<'a>>,
+        rng: &mut Rng
+    ) -> Option<HitRecord<'a>> {
+        if self.density <= 0.0 {
+            return surface;
+        }
+        let distance = -(1.0 - random_f32(rng)).ln() / self.density;
+        let t = distance / ray.direction().length();
+        match surface {
+            Some(rec) if rec.t <= t => Some(rec),
+            None if distance > self.extent => None,
+            _ => Some(scatter_record(ray, t, &self.phase_function)),
+        }
+    }
+
+    /// Fraction of light that makes it `distance` through the atmosphere unscattered.
+    /// Light from the background has crossed `extent` of it.
+    pub fn transmittance(&self, distance: f32) -> f32 {
+        (-self.density.max(0.0) * distance.min(self.extent)).exp()
+    }
+}
+
+/// A scattering event has no surface, so the normal just faces back along the ray.
+fn scatter_record<'a>(
+    ray: &Ray,
+    t: f32,
+    phase_function: &'a Arc<dyn Material + Send + Sync>
+) -> HitRecord<'a> {
+    let normal = -ray.direction().to_unit_vector();
+    HitRecord::new(ray.at(t), t, ray, phase_function, normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        background::Background,
+        camera::Camera,
+        hittable::HittableList,
+        material::Isotropic,
+        rng::seeded_rng,
+        vec3::{ Color, Point3, Vec3 },
+    };
+
+    #[test]
+    fn thin_atmosphere_lets_background_through() {
+        // A black phase function keeps exactly the light that crosses the haze unscattered.
+        let black = Color::new(0.0, 0.0, 0.0);
+        let haze = Atmosphere::new(0.001, 100.0, Arc::new(Isotropic::new(black)));
+        let expected = haze.transmittance(f32::INFINITY);
+        assert!((expected - (-0.1f32).exp()).abs() < 1e-6);
+
+        let (world, lights) = (HittableList::new(), HittableList::new());
+        let background = Background::Solid(Color::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rng = seeded_rng(1);
+        let n = 4000;
+        let total: f32 = (0..n)
+            .map(|_| {
+                let color = Camera::ray_color(
+                    &ray,
+                    10,
+                    &world,
+                    &lights,
+                    &background,
+                    Some(&haze),
+                    None,
+                    &mut rng
+                );
+                color.x()
+            })
+            .sum();
+        assert!((total / (n as f32) - expected).abs() < 0.02);
+    }
+}
@@ -0,0 +1,245 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    hittable::{ HitRecord, Hittable, MaterialIds },
+    interval::Interval,
+    material::{ Dielectric, DiffuseLight, Lambertian, Material, Metal, Principled },
+    random_f32,
+    ray::Ray,
+    rng::Rng,
+    texture::{ ImageTexture, SharedTexture },
+    triangle::Triangle,
+    vec3::{ Color, Point3, Vec3 },
+};
+
+/// A triangle soup with its own BVH, so a large mesh is a single object in the scene.
+pub struct Mesh {
+    bvh: BvhNode,
+    emitters: Option<Arc<dyn Hittable>>,
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        let emitters: Vec<Triangle> = triangles
+            .iter()
+            .filter(|tri| tri.is_emissive())
+            .cloned()
+            .collect();
+        let emitters = (!emitters.is_empty()).then(||
+            Arc::new(Emitters::new(emitters)) as Arc<dyn Hittable>
+        );
+        let objects: Vec<Box<dyn Hittable>> = triangles
+            .into_iter()
+            .map(|tri| Box::new(tri) as Box<dyn Hittable>)
+            .collect();
+        Mesh { bvh: BvhNode::build(objects), emitters }
+    }
+
+    /// Just the faces with an emissive material, for sampling the mesh as a light.
+    pub fn emitters(&self) -> Option<Arc<dyn Hittable>> {
+        self.emitters.clone()
+    }
+
+    /// Loads a Wavefront OBJ file, mapping its MTL materials onto ours.
+    /// Faces without a usable MTL material fall back to `default_mat`.
+    pub fn load_obj(
+        path: &Path,
+        default_mat: Arc<dyn Material + Send + Sync>
+    ) -> Result<Mesh, tobj::LoadError> {
+        let (models, mtl_result) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+        let materials: Vec<Arc<dyn Material + Send + Sync>> = match mtl_result {
+            Ok(mtls) => {
+                let base_dir = path.parent().unwrap_or(Path::new(""));
+                mtls.iter()
+                    .map(|mtl| material_from_mtl(mtl, base_dir))
+                    .collect()
+            }
+            Err(e) => {
+                eprintln!("Ignoring materials of {}: {}", path.display(), e);
+                Vec::new()
+            }
+        };
+
+        let mut triangles = Vec::new();
+        for model in &models {
+            let mesh = &model.mesh;
+            let mat = mesh.material_id
+                .and_then(|id| materials.get(id))
+                .unwrap_or(&default_mat);
+
+            let position = |i: usize| {
+                let p = &mesh.positions[3 * i..3 * i + 3];
+                Point3::new(p[0], p[1], p[2])
+            };
+            let normal = |i: usize| {
+                let n = &mesh.normals[3 * i..3 * i + 3];
+                Vec3::new(n[0], n[1], n[2])
+            };
+            let uv = |i: usize| (mesh.texcoords[2 * i], mesh.texcoords[2 * i + 1]);
+
+            for face in mesh.indices.chunks_exact(3) {
+                let idx = [face[0] as usize, face[1] as usize, face[2] as usize];
+                let mut tri = Triangle::new(idx.map(position), mat.clone());
+                if !mesh.normals.is_empty() {
+                    tri = tri.with_normals(idx.map(normal));
+                }
+                if !mesh.texcoords.is_empty() {
+                    tri = tri.with_uvs(idx.map(uv));
+                }
+                triangles.push(tri);
+            }
+        }
+        Ok(Mesh::new(triangles))
+    }
+}
+
+/// MTL files describe Phong-style materials; this picks the closest of ours.
+fn material_from_mtl(mtl: &tobj::Material, base_dir: &Path) -> Arc<dyn Material + Send + Sync> {
+    let to_color = |c: [f32; 3]| Color::new(c[0], c[1], c[2]);
+
+    if let Some(emit) = mtl.unknown_param.get("Ke").and_then(|ke| parse_color(ke)) {
+        if emit.length_squared() > 0.0 {
+            return Arc::new(DiffuseLight { emit });
+        }
+    }
+
+    let transparent = mtl.dissolve.is_some_and(|d| d < 1.0);
+    if transparent || matches!(mtl.illumination_model, Some(4 | 6 | 7 | 9)) {
+        return Arc::new(Dielectric {
+            refraction_index: mtl.optical_density.unwrap_or(1.5),
+            frostedness: 0.0,
+        });
+    }
+
+    let diffuse = mtl.diffuse.map(to_color).unwrap_or(Color::new(0.8, 0.8, 0.8));
+    let albedo: SharedTexture = match &mtl.diffuse_texture {
+        Some(file) =>
+            match ImageTexture::load(&base_dir.join(file)) {
+                Ok(texture) => Arc::new(texture),
+                Err(e) => {
+                    eprintln!("Ignoring texture {}: {}", file, e);
+                    diffuse.into()
+                }
+            }
+        None => diffuse.into(),
+    };
+
+    // The PBR extension to MTL, as written by Blender and most modern exporters.
+    let pbr = |name: &str| mtl.unknown_param.get(name).and_then(|v| v.trim().parse::<f32>().ok());
+    if ["Pr", "Pm", "Pc"].iter().any(|name| pbr(name).is_some()) {
+        let mut mat = Principled::new(albedo);
+        mat.roughness = pbr("Pr").unwrap_or(mat.roughness);
+        mat.metallic = pbr("Pm").unwrap_or(mat.metallic);
+        mat.clearcoat = pbr("Pc").unwrap_or(mat.clearcoat);
+        mat.clearcoat_roughness = pbr("Pcr").unwrap_or(mat.clearcoat_roughness);
+        return Arc::new(mat);
+    }
+
+    if matches!(mtl.illumination_model, Some(3 | 5)) {
+        let shininess = mtl.shininess.unwrap_or(0.0).clamp(0.0, 1000.0);
+        let specular = mtl.specular.map(to_color).unwrap_or(diffuse);
+        return Arc::new(Metal::new(specular, 1.0 - shininess / 1000.0));
+    }
+
+    Arc::new(Lambertian::new(albedo))
+}
+
+fn parse_color(s: &str) -> Option<Color> {
+    let values: Vec<f32> = s
+        .split_whitespace()
+        .map(|v| v.parse().ok())
+        .collect::<Option<_>>()?;
+    match values.as_slice() {
+        [r, g, b] => Some(Color::new(*r, *g, *b)),
+        [c] => Some(Color::new(*c, *c, *c)),
+        _ => None,
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, ray_t, rng)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.bvh.assign_ids(object_id, material_ids);
+    }
+}
+
+/// The emissive faces of a mesh, sampled in proportion to their area.
+struct Emitters {
+    /// Holds `Emitter`s, so the density of a direction only visits faces along it.
+    bvh: BvhNode,
+    triangles: Vec<Triangle>,
+    /// Running total of the triangles' areas.
+    cdf: Vec<f32>,
+}
+
+impl Emitters {
+    fn new(triangles: Vec<Triangle>) -> Self {
+        let mut total = 0.0;
+        let cdf: Vec<f32> = triangles
+            .iter()
+            .map(|tri| {
+                total += tri.area();
+                total
+            })
+            .collect();
+        let objects: Vec<Box<dyn Hittable>> = triangles
+            .iter()
+            .map(|tri| {
+                let weight = tri.area() / total;
+                Box::new(Emitter { tri: tri.clone(), weight }) as Box<dyn Hittable>
+            })
+            .collect();
+        Emitters { bvh: BvhNode::build(objects), triangles, cdf }
+    }
+}
+
+impl Hittable for Emitters {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, ray_t, rng)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        self.bvh.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let total = self.cdf[self.cdf.len() - 1];
+        let u = random_f32(rng) * total;
+        let k = self.cdf.partition_point(|&c| c <= u).min(self.triangles.len() - 1);
+        self.triangles[k].random(origin, rng)
+    }
+}
+
+/// An emissive face weighted by its share of the emitting area.
+struct Emitter {
+    tri: Triangle,
+    weight: f32,
+}
+
+impl Hittable for Emitter {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord<'_>> {
+        self.tri.hit(ray, ray_t, rng)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.tri.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        self.weight * self.tri.pdf_value(origin, direction)
+    }
+}
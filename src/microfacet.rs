@@ -0,0 +1,111 @@
+use std::f32::consts::PI;
+
+use crate::vec3::{ Color, Vec3, cross, dot };
+
+/// Trowbridge-Reitz (GGX) distribution of microfacet normals. Everything works in a
+/// shading frame with the macro normal along +z and directions pointing away from
+/// the surface.
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha: f32,
+}
+
+impl Ggx {
+    /// `roughness` is the perceptual 0 to 1 value artists use; the distribution's
+    /// width is its square. Fully smooth is clamped to a very narrow lobe.
+    pub fn new(roughness: f32) -> Self {
+        let roughness = roughness.clamp(0.0, 1.0);
+        Ggx { alpha: (roughness * roughness).max(1e-3) }
+    }
+
+    pub fn d(&self, h: Vec3) -> f32 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let t = h.z() * h.z() * (a2 - 1.0) + 1.0;
+        a2 / (PI * t * t)
+    }
+
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2 = w.z() * w.z();
+        if cos2 <= 0.0 {
+            return f32::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Smith masking of one direction.
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height-correlated Smith masking and shadowing.
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of `sample_visible` returning `h` for `wo`.
+    pub fn visible_pdf(&self, wo: Vec3, h: Vec3) -> f32 {
+        (self.g1(wo) * dot(&wo, &h).max(0.0) * self.d(h)) / wo.z().abs()
+    }
+
+    /// Samples a microfacet normal as seen from `wo` (Heitz 2018), so facets facing
+    /// away from the viewer are never generated.
+    pub fn sample_visible(&self, wo: Vec3, u1: f32, u2: f32) -> Vec3 {
+        let vh = Vec3::new(self.alpha * wo.x(), self.alpha * wo.y(), wo.z()).to_unit_vector();
+        let len_sq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len_sq > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len_sq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = cross(&vh, &t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        Vec3::new(self.alpha * nh.x(), self.alpha * nh.y(), nh.z().max(1e-6)).to_unit_vector()
+    }
+}
+
+pub fn fresnel_schlick(f0: Color, cos_theta: f32) -> Color {
+    let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 + (Color::new(1.0, 1.0, 1.0) - f0) * weight
+}
+
+/// Unpolarised Fresnel reflectance for light arriving at `cos_theta` to a boundary
+/// where the far side's index is `eta` times the near side's. 1 past the critical angle.
+pub fn fresnel_dielectric(cos_theta: f32, eta: f32) -> f32 {
+    let (cos_i, eta) = if cos_theta < 0.0 { (-cos_theta, 1.0 / eta) } else { (cos_theta, eta) };
+    let cos_i = cos_i.min(1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+/// Mirrors `wo` about `h`.
+pub fn reflect(wo: Vec3, h: Vec3) -> Vec3 {
+    2.0 * dot(&wo, &h) * h - wo
+}
+
+/// Bends `wo` through a facet with normal `h` on its side, or `None` on total
+/// internal reflection. `eta` is as for `fresnel_dielectric`.
+pub fn refract(wo: Vec3, h: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = dot(&wo, &h);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + (cos_i / eta - cos_t) * h)
+}
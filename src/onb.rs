@@ -0,0 +1,28 @@
+use crate::vec3::{ Vec3, cross, dot };
+
+/// Orthonormal basis with `w` along a given direction.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Self {
+        let w = n.to_unit_vector();
+        let a = if w.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = cross(&w, &a).to_unit_vector();
+        let u = cross(&w, &v);
+        Onb { u, v, w }
+    }
+
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        v.x() * self.u + v.y() * self.v + v.z() * self.w
+    }
+
+    /// Inverse of `transform`: world space `v` in this basis' coordinates.
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(dot(&v, &self.u), dot(&v, &self.v), dot(&v, &self.w))
+    }
+}
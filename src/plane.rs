@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{ HitRecord, Hittable, MaterialIds },
+    interval::Interval,
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    rng::Rng,
+    vec3::{ Point3, Vec3, dot },
+};
+
+/// Infinite plane through `point`. Its UVs are world-space distances from `point`
+/// along two in-plane axes, so textures repeat only if they tile by themselves.
+pub struct Plane {
+    point: Point3,
+    frame: Onb,
+    mat: Arc<dyn Material + Send + Sync>,
+    bbox: Aabb,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        let frame = Onb::new(normal);
+        let n = frame.w;
+        // Only a plane facing along an axis is bounded, and only along that axis.
+        let extent = |axis: f32, other_a: f32, other_b: f32, coord: f32| {
+            if other_a == 0.0 && other_b == 0.0 && axis != 0.0 {
+                Interval::new(coord, coord)
+            } else {
+                Interval::universe()
+            }
+        };
+        let bbox = Aabb::new(
+            extent(n.x(), n.y(), n.z(), point.x()),
+            extent(n.y(), n.x(), n.z(), point.y()),
+            extent(n.z(), n.x(), n.y(), point.z())
+        );
+        Plane { point, frame, mat, bbox, object_id: 0, material_id: 0 }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord<'_>> {
+        let normal = self.frame.w;
+        let denom = dot(&normal, &ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = dot(&normal, &(self.point - ray.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let offset = p - self.point;
+        let mut rec = HitRecord::new(p, t, ray, &self.mat, normal);
+        rec.u = dot(&offset, &self.frame.u);
+        rec.v = dot(&offset, &self.frame.v);
+        rec.object_id = self.object_id;
+        rec.material_id = self.material_id;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.object_id = object_id;
+        self.material_id = material_ids.id(&self.mat);
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{ HitRecord, Hittable, HittableList, MaterialIds },
+    interval::Interval,
+    material::Material,
+    random_f32,
+    ray::Ray,
+    rng::Rng,
+    vec3::{ Point3, Vec3, cross, dot },
+};
+
+/// Parallelogram with corner `q` and edges `u` and `v`. The normal follows `u × v`.
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    /// `n / (n · n)` for the unnormalised normal `n`, to get the planar coordinates.
+    w: Vec3,
+    normal: Vec3,
+    d: f32,
+    area: f32,
+    mat: Arc<dyn Material + Send + Sync>,
+    bbox: Aabb,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material + Send + Sync>) -> Self {
+        let n = cross(&u, &v);
+        let normal = n.to_unit_vector();
+        let bbox = Aabb::surrounding(
+            &Aabb::from_points(q, q + u + v),
+            &Aabb::from_points(q + u, q + v)
+        );
+        Quad {
+            q,
+            u,
+            v,
+            w: n / dot(&n, &n),
+            normal,
+            d: dot(&normal, &q),
+            area: n.length(),
+            mat,
+            bbox,
+            object_id: 0,
+            material_id: 0,
+        }
+    }
+
+    fn intersect(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = dot(&self.normal, &ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - dot(&self.normal, &ray.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let p = ray.at(t);
+        let planar = p - self.q;
+        let alpha = dot(&self.w, &cross(&planar, &self.v));
+        let beta = dot(&self.w, &cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(p, t, ray, &self.mat, self.normal);
+        rec.u = alpha;
+        rec.v = beta;
+        rec.object_id = self.object_id;
+        rec.material_id = self.material_id;
+        Some(rec)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord<'_>> {
+        self.intersect(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let ray = Ray::new(origin, direction);
+        let Some(rec) = self.intersect(&ray, Interval::new(0.001, f32::INFINITY)) else {
+            return 0.0;
+        };
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (dot(&direction, &self.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let p = self.q + random_f32(rng) * self.u + random_f32(rng) * self.v;
+        p - origin
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.object_id = object_id;
+        self.material_id = material_ids.id(&self.mat);
+    }
+}
+
+/// Axis-aligned box with opposite corners `a` and `b`.
+pub fn axis_aligned_box(
+    a: Point3,
+    b: Point3,
+    mat: Arc<dyn Material + Send + Sync>
+) -> HittableList {
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+    oriented_box(
+        min,
+        Vec3::new(max.x() - min.x(), 0.0, 0.0),
+        Vec3::new(0.0, max.y() - min.y(), 0.0),
+        Vec3::new(0.0, 0.0, max.z() - min.z()),
+        mat
+    )
+}
+
+/// Box spanned by the edges `u`, `v` and `w` from `corner`, as six outward facing
+/// quads. The edges should form a right-handed frame, like x, y and z.
+pub fn oriented_box(
+    corner: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    mat: Arc<dyn Material + Send + Sync>
+) -> HittableList {
+    let far = corner + u + v + w;
+    let mut sides = HittableList::new();
+    sides.add(Box::new(Quad::new(corner, v, u, mat.clone())));
+    sides.add(Box::new(Quad::new(corner, w, v, mat.clone())));
+    sides.add(Box::new(Quad::new(corner, u, w, mat.clone())));
+    sides.add(Box::new(Quad::new(far, -u, -v, mat.clone())));
+    sides.add(Box::new(Quad::new(far, -v, -w, mat.clone())));
+    sides.add(Box::new(Quad::new(far, -w, -u, mat)));
+    sides
+}
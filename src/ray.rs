@@ -1,11 +1,17 @@
 use crate::{ vec3::{ Point3, Vec3 } };
 
+/// The third field is the time the ray was sent, somewhere in the camera's shutter
+/// interval, which moving objects use to decide where they are.
 #[derive(Debug, Clone, Copy)]
-pub struct Ray(Point3, Vec3);
+pub struct Ray(Point3, Vec3, f32);
 
 impl Ray {
     pub const fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray(origin, direction)
+        Ray(origin, direction, 0.0)
+    }
+
+    pub const fn with_time(origin: Point3, direction: Vec3, time: f32) -> Ray {
+        Ray(origin, direction, time)
     }
 
     pub fn origin(&self) -> Point3 {
@@ -16,6 +22,10 @@ impl Ray {
         self.1
     }
 
+    pub fn time(&self) -> f32 {
+        self.2
+    }
+
     pub fn at(&self, t: f32) -> Point3 {
         self.origin() + self.direction() * t
     }
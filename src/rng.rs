@@ -0,0 +1,22 @@
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+pub type Rng = Pcg32;
+
+pub fn seeded_rng(seed: u64) -> Rng {
+    Rng::seed_from_u64(seed)
+}
+
+/// Independent stream for one sample of one pixel, so the result never depends on
+/// which rayon thread ends up rendering it or in what order.
+pub fn pixel_rng(seed: u64, pixel: usize, sample: u32) -> Rng {
+    let stream = splitmix64(((pixel as u64) << 32) | (sample as u64));
+    Rng::seed_from_u64(splitmix64(seed ^ stream))
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
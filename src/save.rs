@@ -1,49 +1,253 @@
-use std::fs::OpenOptions;
-use std::io::prelude::*;
+use std::fs::File;
+use std::io::{ self, BufWriter, prelude::* };
+use std::path::{ Path, PathBuf };
+use image::{ ImageBuffer, Rgb };
+#[cfg(feature = "gui")]
 use rfd::FileDialog;
-use crate::camera::Camera;
+use crate::{ Size, camera::{ Aov, Camera }, vec3::Color };
 
-pub fn save_image(camera: &Camera) {
+/// With `png16` a `.png` file is written with 16 bits per channel.
+#[cfg(feature = "gui")]
+pub fn save_image(camera: &Camera, png16: bool) {
     println!("Saving file to with name ");
-    let files = FileDialog::new().set_file_name("render.ppm").save_file();
+    let png_filter = if png16 { "PNG image (16-bit)" } else { "PNG image" };
+    let files = FileDialog::new()
+        .set_file_name("render.png")
+        .add_filter(png_filter, &["png"])
+        .add_filter("OpenEXR image (linear)", &["exr"])
+        .add_filter("PPM image", &["ppm"])
+        .save_file();
 
     match files {
         None => println!("The user cancelled the save."),
         Some(path_buf) => {
-            let mut file = match
-                OpenOptions::new().write(true).create(true).truncate(true).open(path_buf)
-            {
-                Ok(f) => f,
-                Err(_) => {
-                    return;
-                }
-            };
+            let result = ImageFormat::from_path(&path_buf).and_then(|format| {
+                let format = match format {
+                    ImageFormat::Png8 if png16 => ImageFormat::Png16,
+                    format => format,
+                };
+                write_image(camera, &path_buf, format)
+            });
+            if let Err(e) = result {
+                println!("Failed to save {}: {}", path_buf.display(), e);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Png8,
+    Png16,
+    /// 32-bit float linear radiance, without gamma or clamping.
+    Exr,
+}
 
-            writeln!(file, "P3").expect("write failed");
-            writeln!(file, "{} {}", camera.image_size.w, camera.image_size.h).expect(
-                "write failed"
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let ext = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+        match ext.as_deref() {
+            Some("ppm") | None => Ok(ImageFormat::Ppm),
+            Some("png") => Ok(ImageFormat::Png8),
+            Some("exr") => Ok(ImageFormat::Exr),
+            Some(ext) =>
+                Err(
+                    io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        format!("unsupported image format '.{}', expected .png, .exr or .ppm", ext)
+                    )
+                ),
+        }
+    }
+}
+
+pub fn write_image(camera: &Camera, path: &Path, format: ImageFormat) -> io::Result<()> {
+    match format {
+        ImageFormat::Ppm => write_ppm(camera.image_size, path, |i| display_rgb(camera, i)),
+        ImageFormat::Png8 => write_png8(camera.image_size, path, |i| display_rgb(camera, i)),
+        ImageFormat::Png16 => {
+            let img: ImageBuffer<Rgb<u16>, Vec<u16>> = ImageBuffer::from_fn(
+                camera.image_size.w as u32,
+                camera.image_size.h as u32,
+                |x, y| {
+                    let c = camera.pixel_radiance(pixel_index(camera, x, y)).gamma_encoded();
+                    Rgb([c.x(), c.y(), c.z()].map(|v| (65535.99 * v) as u16))
+                }
             );
-            writeln!(file, "255").expect("write failed");
-
-            let inv_samples = if camera.sample_current > 0 {
-                1.0 / (camera.sample_current as f32)
-            } else {
-                1.0
-            };
-
-            for j in 0..camera.image_size.h {
-                for i in 0..camera.image_size.w {
-                    let pixel_color =
-                        camera.color_buffer[j * camera.image_size.w + i] * inv_samples;
-                    let r = pixel_color.x().sqrt();
-                    let g = pixel_color.y().sqrt();
-                    let b = pixel_color.z().sqrt();
-                    let ir = (255.999 * r) as u8;
-                    let ig = (255.999 * g) as u8;
-                    let ib = (255.999 * b) as u8;
-                    writeln!(file, "{} {} {}", ir, ig, ib).expect("write failed");
+            img.save_with_format(path, image::ImageFormat::Png).map_err(io::Error::other)
+        }
+        ImageFormat::Exr => {
+            let img: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::from_fn(
+                camera.image_size.w as u32,
+                camera.image_size.h as u32,
+                |x, y| {
+                    let c = camera.pixel_radiance(pixel_index(camera, x, y));
+                    Rgb([c.x(), c.y(), c.z()])
                 }
-            }
+            );
+            img.save_with_format(path, image::ImageFormat::OpenExr).map_err(io::Error::other)
+        }
+    }
+}
+
+/// Writes the beauty image plus every AOV. EXR output is a single multi-layer file
+/// with raw AOV data; other formats get an 8-bit preview per AOV next to `path`,
+/// e.g. `render.normal.png`.
+pub fn write_image_with_aovs(
+    camera: &Camera,
+    path: &Path,
+    format: ImageFormat
+) -> io::Result<()> {
+    if format == ImageFormat::Exr {
+        return write_layered_exr(camera, path);
+    }
+    write_image(camera, path, format)?;
+    for aov in Aov::ALL {
+        let pixels = camera.aov_pixels(aov);
+        let aov_path = aov_path(path, aov);
+        match format {
+            ImageFormat::Ppm => write_ppm(camera.image_size, &aov_path, |i| unpack(pixels[i]))?,
+            _ => write_png8(camera.image_size, &aov_path, |i| unpack(pixels[i]))?,
+        }
+    }
+    Ok(())
+}
+
+/// `render.png` becomes `render.normal.png`.
+fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, aov.name(), ext.to_string_lossy()),
+        None => format!("{}.{}", stem, aov.name()),
+    };
+    path.with_file_name(name)
+}
+
+fn write_layered_exr(camera: &Camera, path: &Path) -> io::Result<()> {
+    use exr::prelude::*;
+
+    let mut layers = vec![
+        exr_layer(camera.image_size, "beauty", &["R", "G", "B"], |i| camera.pixel_radiance(i))
+    ];
+    for aov in Aov::ALL {
+        let channels: &[&str] = match aov {
+            Aov::Normal => &["X", "Y", "Z"],
+            Aov::Depth => &["Z"],
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+        };
+        let layer = exr_layer(camera.image_size, aov.name(), channels, |i|
+            camera.aov_value(aov, i)
+        );
+        layers.push(layer);
+    }
+
+    let bounds = IntegerBounds::from_dimensions((camera.image_size.w, camera.image_size.h));
+    Image::from_layers(ImageAttributes::new(bounds), layers)
+        .write()
+        .to_file(path)
+        .map_err(io::Error::other)
+}
+
+/// One float channel per name, taken from the x, y and z of `value` in turn.
+fn exr_layer(
+    size: Size,
+    name: &str,
+    channels: &[&str],
+    value: impl Fn(usize) -> Color
+) -> exr::prelude::Layer<exr::prelude::AnyChannels<exr::prelude::FlatSamples>> {
+    use exr::prelude::*;
+
+    let values: Vec<Color> = (0..size.area()).map(value).collect();
+    let channels: Vec<AnyChannel<FlatSamples>> = channels
+        .iter()
+        .enumerate()
+        .map(|(c, &channel)| {
+            let samples = values
+                .iter()
+                .map(|v| [v.x(), v.y(), v.z()][c])
+                .collect();
+            AnyChannel::new(channel, FlatSamples::F32(samples))
+        })
+        .collect();
+    Layer::new(
+        (size.w, size.h),
+        LayerAttributes::named(name),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels.into())
+    )
+}
+
+fn pixel_index(camera: &Camera, x: u32, y: u32) -> usize {
+    (y as usize) * camera.image_size.w + (x as usize)
+}
+
+/// Same 8-bit values the viewer shows for this pixel.
+fn display_rgb(camera: &Camera, i: usize) -> [u8; 3] {
+    unpack(camera.pixel_radiance(i).to_u32())
+}
+
+fn unpack(packed: u32) -> [u8; 3] {
+    [(packed >> 16) as u8, (packed >> 8) as u8, packed as u8]
+}
+
+fn write_png8(size: Size, path: &Path, rgb: impl Fn(usize) -> [u8; 3]) -> io::Result<()> {
+    let img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::from_fn(
+        size.w as u32,
+        size.h as u32,
+        |x, y| Rgb(rgb((y as usize) * size.w + (x as usize)))
+    );
+    img.save_with_format(path, image::ImageFormat::Png).map_err(io::Error::other)
+}
+
+fn write_ppm(size: Size, path: &Path, rgb: impl Fn(usize) -> [u8; 3]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    writeln!(file, "P3")?;
+    writeln!(file, "{} {}", size.w, size.h)?;
+    writeln!(file, "255")?;
+
+    for j in 0..size.h {
+        for i in 0..size.w {
+            let [r, g, b] = rgb(j * size.w + i);
+            writeln!(file, "{} {} {}", r, g, b)?;
+        }
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ Size, scene::Scene };
+
+    #[test]
+    fn saved_png_matches_displayed_buffer() {
+        let size = Size { w: 24, h: 16 };
+        let mut scene = Scene::create_scene2(size, 0);
+        let mut buffer = vec![0; size.area()];
+        // The first passes are low resolution previews that skip `color_buffer`.
+        for _ in 0..6 {
+            scene.camera.render(
+                &scene.world,
+                &scene.lights,
+                &scene.background,
+                &mut buffer
+            );
+        }
+
+        let path = std::env::temp_dir().join(
+            format!("rusttracer_save_test_{}.png", std::process::id())
+        );
+        write_image(&scene.camera, &path, ImageFormat::Png8).unwrap();
+        let saved = image::open(&path).unwrap().into_rgb8();
+        std::fs::remove_file(&path).unwrap();
+
+        for (x, y, px) in saved.enumerate_pixels() {
+            let displayed = buffer[(y as usize) * size.w + (x as usize)];
+            let expected = [(displayed >> 16) as u8, (displayed >> 8) as u8, displayed as u8];
+            assert_eq!(px.0, expected, "pixel ({}, {}) differs", x, y);
         }
     }
 }
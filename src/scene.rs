@@ -1,53 +1,70 @@
+pub mod loader;
+
 use crate::{
     Color,
+    background::Background,
     Point3,
     Size,
     camera::Camera,
     hittable::HittableList,
     material::{ Dielectric, Lambertian, Material, Metal },
+    plane::Plane,
     random_f32,
     random_f32_range,
+    rng::seeded_rng,
     sphere::Sphere,
+    vec3::Vec3,
 };
+use std::path::Path;
 use std::sync::Arc;
+use loader::SceneError;
 
 pub struct Scene {
     pub camera: Camera,
     pub world: HittableList,
+    /// Emissive shapes sampled directly for next event estimation. They must also be in `world`.
+    pub lights: HittableList,
+    pub background: Background,
 }
 
 impl Scene {
-    pub fn create_scene1(size: Size) -> Scene {
+    pub fn from_file(path: &Path, size: Size) -> Result<Scene, SceneError> {
+        loader::load_scene(path, size)
+    }
+
+    pub fn create_scene1(size: Size, seed: u64) -> Scene {
+        let mut rng = seeded_rng(seed);
         let mut world = HittableList::new();
 
-        let ground_material: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian {
-            albedo: Color::new(0.5, 0.5, 0.5),
-        });
-        world.add(Box::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material)));
+        let ground_material: Arc<dyn Material + Send + Sync> = Arc::new(
+            Lambertian::new(Color::new(0.5, 0.5, 0.5))
+        );
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        world.add(Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), up, ground_material)));
 
         for a in -6..6 {
             for b in -6..6 {
-                let choose_mat = random_f32();
+                let choose_mat = random_f32(&mut rng);
                 let center = Point3::new(
-                    (a as f32) + 0.9 * random_f32(),
+                    (a as f32) + 0.9 * random_f32(&mut rng),
                     0.2,
-                    (b as f32) + 0.9 * random_f32()
+                    (b as f32) + 0.9 * random_f32(&mut rng)
                 );
 
                 if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                     let sphere_material: Arc<dyn Material + Send + Sync>;
 
                     if choose_mat < 0.8 {
-                        let albedo = Color::random() * Color::random();
-                        sphere_material = Arc::new(Lambertian { albedo });
+                        let albedo = Color::random(&mut rng) * Color::random(&mut rng);
+                        sphere_material = Arc::new(Lambertian::new(albedo));
                     } else if choose_mat < 0.95 {
-                        let albedo = Color::random_range(0.5, 1.0);
-                        let fuzz = random_f32_range(0.0, 0.5);
-                        sphere_material = Arc::new(Metal { albedo, fuzziness: fuzz });
+                        let albedo = Color::random_range(&mut rng, 0.5, 1.0);
+                        let fuzz = random_f32_range(&mut rng, 0.0, 0.5);
+                        sphere_material = Arc::new(Metal::new(albedo, fuzz));
                     } else {
                         sphere_material = Arc::new(Dielectric {
-                            refraction_index: random_f32_range(0.5, 2.5),
-                            frostedness: random_f32_range(0.0, 0.05),
+                            refraction_index: random_f32_range(&mut rng, 0.5, 2.5),
+                            frostedness: random_f32_range(&mut rng, 0.0, 0.05),
                         });
                     }
 
@@ -62,32 +79,36 @@ impl Scene {
         });
         world.add(Box::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, material1)));
 
-        let material2: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian {
-            albedo: Color::new(0.4, 0.2, 0.1),
-        });
+        let material2: Arc<dyn Material + Send + Sync> = Arc::new(
+            Lambertian::new(Color::new(0.4, 0.2, 0.1))
+        );
         world.add(Box::new(Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, material2)));
 
-        let material3: Arc<dyn Material + Send + Sync> = Arc::new(Metal {
-            albedo: Color::new(0.7, 0.6, 0.5),
-            fuzziness: 0.0,
-        });
+        let material3: Arc<dyn Material + Send + Sync> = Arc::new(
+            Metal::new(Color::new(0.7, 0.6, 0.5), 0.0)
+        );
         world.add(Box::new(Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material3)));
 
-        let cam = Camera::new(20.0, size, 2000, 4);
+        let mut cam = Camera::new(20.0, size, 2000, 4);
+        cam.seed = seed;
 
         Scene {
             camera: cam,
-            world,
+            world: world.into_bvh(),
+            lights: HittableList::new(),
+            background: Background::Sky,
         }
     }
 
-    pub fn create_scene2(size: Size) -> Scene {
+    pub fn create_scene2(size: Size, seed: u64) -> Scene {
+        let mut rng = seeded_rng(seed);
         let mut world = HittableList::new();
 
-        let ground_material: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian {
-            albedo: Color::new(0.2, 0.3, 0.4),
-        });
-        world.add(Box::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material)));
+        let ground_material: Arc<dyn Material + Send + Sync> = Arc::new(
+            Lambertian::new(Color::new(0.2, 0.3, 0.4))
+        );
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        world.add(Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), up, ground_material)));
 
         let glass_outer: Arc<dyn Material + Send + Sync> = Arc::new(Dielectric {
             refraction_index: 1.5,
@@ -108,10 +129,9 @@ impl Scene {
                 0.7,
                 0.5 + 0.5 * (1.0 - (i as f32) / (num_ring_spheres as f32))
             );
-            let metal_mat: Arc<dyn Material + Send + Sync> = Arc::new(Metal {
-                albedo: metal_color,
-                fuzziness: random_f32_range(0.0, 0.2),
-            });
+            let metal_mat: Arc<dyn Material + Send + Sync> = Arc::new(
+                Metal::new(metal_color, random_f32_range(&mut rng, 0.0, 0.2))
+            );
             world.add(Box::new(Sphere::new(Point3::new(x, 0.4, z), 0.4, metal_mat)));
         }
 
@@ -147,34 +167,34 @@ impl Scene {
             let x = angle.cos() * radius;
             let z = angle.sin() * radius;
 
-            let lamb_mat: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian {
-                albedo: *color,
-            });
+            let lamb_mat: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::new(*color));
             world.add(Box::new(Sphere::new(Point3::new(x, 0.5, z), 0.5, lamb_mat)));
         }
 
-        let mirror: Arc<dyn Material + Send + Sync> = Arc::new(Metal {
-            albedo: Color::new(0.95, 0.95, 0.95),
-            fuzziness: 0.0,
-        });
+        let mirror: Arc<dyn Material + Send + Sync> = Arc::new(
+            Metal::new(Color::new(0.95, 0.95, 0.95), 0.0)
+        );
         world.add(Box::new(Sphere::new(Point3::new(0.0, 2.0, -8.0), 2.0, mirror)));
 
-        let cam = Camera::new(20.0, size, 2000, 4);
+        let mut cam = Camera::new(20.0, size, 2000, 4);
+        cam.seed = seed;
 
         Scene {
             camera: cam,
-            world,
+            world: world.into_bvh(),
+            lights: HittableList::new(),
+            background: Background::Sky,
         }
     }
 
-    pub fn create_scene3(size: Size) -> Scene {
+    pub fn create_scene3(size: Size, seed: u64) -> Scene {
         let mut world = HittableList::new();
 
-        let ground_material: Arc<dyn Material + Send + Sync> = Arc::new(Metal {
-            albedo: Color::new(0.3, 0.3, 0.35),
-            fuzziness: 0.4,
-        });
-        world.add(Box::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material)));
+        let ground_material: Arc<dyn Material + Send + Sync> = Arc::new(
+            Metal::new(Color::new(0.3, 0.3, 0.35), 0.4)
+        );
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        world.add(Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), up, ground_material)));
 
         let tall_glass1: Arc<dyn Material + Send + Sync> = Arc::new(Dielectric {
             refraction_index: 1.5,
@@ -192,10 +212,9 @@ impl Scene {
         world.add(Box::new(Sphere::new(Point3::new(3.0, 1.5, 0.0), 0.5, tall_glass2.clone())));
         world.add(Box::new(Sphere::new(Point3::new(3.0, 2.5, 0.0), 0.5, tall_glass2)));
 
-        let center_metal: Arc<dyn Material + Send + Sync> = Arc::new(Metal {
-            albedo: Color::new(0.9, 0.7, 0.3),
-            fuzziness: 0.0,
-        });
+        let center_metal: Arc<dyn Material + Send + Sync> = Arc::new(
+            Metal::new(Color::new(0.9, 0.7, 0.3), 0.0)
+        );
         world.add(Box::new(Sphere::new(Point3::new(0.0, 1.5, 0.0), 1.5, center_metal)));
 
         let floating_spheres = vec![
@@ -205,9 +224,7 @@ impl Scene {
         ];
 
         for (pos, color) in floating_spheres {
-            let lamb: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian {
-                albedo: color,
-            });
+            let lamb: Arc<dyn Material + Send + Sync> = Arc::new(Lambertian::new(color));
             world.add(Box::new(Sphere::new(pos, 0.5, lamb)));
         }
 
@@ -218,10 +235,7 @@ impl Scene {
             let z = angle.sin() * orbit_radius;
 
             let mat: Arc<dyn Material + Send + Sync> = if i % 2 == 0 {
-                Arc::new(Metal {
-                    albedo: Color::new(0.8, 0.8, 0.9),
-                    fuzziness: 0.1,
-                })
+                Arc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 0.1))
             } else {
                 Arc::new(Dielectric {
                     refraction_index: 2.0,
@@ -237,11 +251,14 @@ impl Scene {
         });
         world.add(Box::new(Sphere::new(Point3::new(0.0, 0.8, -6.0), 1.5, large_frosted)));
 
-        let cam = Camera::new(20.0, size, 2000, 4);
+        let mut cam = Camera::new(20.0, size, 2000, 4);
+        cam.seed = seed;
 
         Scene {
             camera: cam,
-            world,
+            world: world.into_bvh(),
+            lights: HittableList::new(),
+            background: Background::Sky,
         }
     }
 }
@@ -0,0 +1,1166 @@
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+
+use toml::Spanned;
+use toml::de::{ DeTable, DeValue };
+
+use crate::{
+    Size,
+    aperture::{ Aperture, ApertureMask },
+    background::{ Background, EnvironmentMap },
+    camera::{ Camera, Projection, Stereo, StereoLayout },
+    disk::Disk,
+    hittable::{ Hittable, HittableList },
+    instance::Instance,
+    mat4::Mat4,
+    mesh::Mesh,
+    material::{
+        Conductor,
+        Dielectric,
+        DiffuseLight,
+        HenyeyGreenstein,
+        Isotropic,
+        Lambertian,
+        Material,
+        Metal,
+        Principled,
+        RoughDielectric,
+    },
+    medium::{ Atmosphere, ConstantMedium },
+    plane::Plane,
+    quad::{ Quad, axis_aligned_box },
+    scene::Scene,
+    sky::PhysicalSky,
+    sphere::{ MovingSphere, Sphere },
+    texture::{ Checker, ImageTexture, SharedTexture },
+    vec3::{ Color, Point3, Vec3 },
+};
+
+type SharedMaterial = Arc<dyn Material + Send + Sync>;
+
+const BACKGROUND_TYPES: &[&str] = &["Sky", "Solid", "Environment", "PhysicalSky"];
+const MATERIAL_TYPES: &[&str] = &[
+    "Lambertian",
+    "Metal",
+    "Dielectric",
+    "DiffuseLight",
+    "Conductor",
+    "RoughDielectric",
+    "Principled",
+    "Isotropic",
+    "HenyeyGreenstein",
+];
+const OBJECT_TYPES: &[&str] = &[
+    "Sphere",
+    "MovingSphere",
+    "Plane",
+    "Quad",
+    "Box",
+    "Disk",
+    "Mesh",
+    "Instance",
+    "ConstantMedium",
+];
+
+/// An entry of `[shapes]`: geometry built once and placed by any number of instances.
+struct Shape {
+    object: Arc<dyn Hittable>,
+    emission: Emission,
+}
+
+/// Which part of an object is sampled as a light.
+#[derive(Clone)]
+enum Emission {
+    None,
+    Whole,
+    /// Only this, e.g. the emissive faces of a mesh.
+    Part(Arc<dyn Hittable>),
+}
+
+impl Emission {
+    fn of(mat: &SharedMaterial) -> Self {
+        if mat.is_emissive() { Emission::Whole } else { Emission::None }
+    }
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+    pub file: PathBuf,
+    pub line: usize,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if self.line > 0 {
+            write!(f, ":{}", self.line)?;
+        }
+        write!(f, ": ")?;
+        if let Some(key) = &self.key {
+            write!(f, "`{}`: ", key)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+pub fn load_scene(path: &Path, size: Size) -> Result<Scene, SceneError> {
+    let src = fs::read_to_string(path).map_err(|e| SceneError {
+        file: path.to_path_buf(),
+        line: 0,
+        key: None,
+        message: e.to_string(),
+    })?;
+    let loader = Loader { path, src: &src };
+    loader.load(size)
+}
+
+struct Loader<'a> {
+    path: &'a Path,
+    src: &'a str,
+}
+
+impl<'a> Loader<'a> {
+    fn load(&self, size: Size) -> Result<Scene, SceneError> {
+        let document = DeTable::parse(self.src).map_err(|e| {
+            let span = e.span().unwrap_or(0..0);
+            self.error(span, None, e.message().to_string())
+        })?;
+        let root = document.get_ref();
+        self.check_keys(root, "", &[
+            "camera",
+            "background",
+            "textures",
+            "materials",
+            "shapes",
+            "objects",
+            "atmosphere",
+        ])?;
+
+        let mut camera = match find(root, "camera") {
+            Some(value) => self.camera(value, size)?,
+            None => Camera::new(20.0, size, 2000, 4),
+        };
+
+        let background = match find(root, "background") {
+            Some(value) => self.background(value)?,
+            None => Background::Sky,
+        };
+
+        let mut textures: Vec<(String, SharedTexture)> = Vec::new();
+        if let Some(value) = find(root, "textures") {
+            let table = self.table(value, "textures")?;
+            for (name, value) in table.iter() {
+                let key = format!("textures.{}", name.get_ref());
+                textures.push((name.get_ref().to_string(), self.texture(value, &key)?));
+            }
+        }
+
+        let mut materials: Vec<(String, SharedMaterial)> = Vec::new();
+        if let Some(value) = find(root, "materials") {
+            let table = self.table(value, "materials")?;
+            for (name, value) in table.iter() {
+                let key = format!("materials.{}", name.get_ref());
+                let material = self.material(value, &key, &textures)?;
+                materials.push((name.get_ref().to_string(), material));
+            }
+        }
+
+        if let Some(value) = find(root, "atmosphere") {
+            camera.atmosphere = Some(self.atmosphere(value, &materials)?);
+        }
+
+        let mut world = HittableList::new();
+        let mut lights = HittableList::new();
+        let mut shapes: Vec<(String, Shape)> = Vec::new();
+        if let Some(value) = find(root, "shapes") {
+            let table = self.table(value, "shapes")?;
+            for (name, value) in table.iter() {
+                let key = format!("shapes.{}", name.get_ref());
+                let (mut object, emission) = self.shape(value, &key, &materials, &shapes)?;
+                world.assign_material_ids(object.as_mut());
+                let shape = Shape { object: Arc::from(object), emission };
+                shapes.push((name.get_ref().to_string(), shape));
+            }
+        }
+
+        if let Some(value) = find(root, "objects") {
+            let DeValue::Array(objects) = value.get_ref() else {
+                return Err(self.type_error(value, "objects", "an array of tables"));
+            };
+            for (i, value) in objects.iter().enumerate() {
+                let key = format!("objects[{}]", i);
+                self.object(value, &key, &materials, &shapes, &mut world, &mut lights)?;
+            }
+        }
+
+        Ok(Scene {
+            camera,
+            world: world.into_bvh(),
+            lights,
+            background,
+        })
+    }
+
+    fn camera(&self, value: &Spanned<DeValue>, size: Size) -> Result<Camera, SceneError> {
+        let table = self.table(value, "camera")?;
+        self.check_keys(table, "camera", &[
+            "fov",
+            "focus_dist",
+            "defocus_angle",
+            "position",
+            "yaw",
+            "pitch",
+            "sample_max",
+            "noise_threshold",
+            "seed",
+            "shutter_open",
+            "shutter_close",
+            "projection",
+            "stereo",
+            "aperture",
+            "anamorphic_squeeze",
+        ])?;
+
+        let fov = self.opt_f32(table, "camera", "fov")?.unwrap_or(20.0);
+        let sample_max = self.opt_u16(table, "camera", "sample_max")?.unwrap_or(2000);
+        let mut camera = Camera::new(fov, size, sample_max, 4);
+
+        if let Some(seed) = self.opt_u64(table, "camera", "seed")? {
+            camera.seed = seed;
+        }
+        camera.noise_threshold = self.opt_f32(table, "camera", "noise_threshold")?;
+        if let Some(value) = find(table, "projection") {
+            let key = join("camera", "projection");
+            let DeValue::String(name) = value.get_ref() else {
+                return Err(self.type_error(value, &key, "a string"));
+            };
+            camera.projection = Projection::ALL
+                .into_iter()
+                .find(|projection| projection.name() == name.as_ref())
+                .ok_or_else(|| {
+                    let names: Vec<_> = Projection::ALL.iter().map(Projection::name).collect();
+                    self.error(
+                        value.span(),
+                        Some(key),
+                        format!(
+                            "unknown projection '{}', expected one of: {}",
+                            name,
+                            names.join(", ")
+                        )
+                    )
+                })?;
+        }
+        camera.shutter_open = self.opt_f32(table, "camera", "shutter_open")?.unwrap_or(0.0);
+        camera.shutter_close = self
+            .opt_f32(table, "camera", "shutter_close")?
+            .unwrap_or(camera.shutter_open);
+        if let Some(focus_dist) = self.opt_f32(table, "camera", "focus_dist")? {
+            camera.focus_dist = focus_dist;
+        }
+        if let Some(defocus_angle) = self.opt_f32(table, "camera", "defocus_angle")? {
+            camera.defocus_angle = defocus_angle;
+        }
+        if let Some(value) = find(table, "aperture") {
+            camera.aperture = self.aperture(value)?;
+        }
+        if let Some(squeeze) = self.opt_f32(table, "camera", "anamorphic_squeeze")? {
+            if squeeze <= 0.0 {
+                let span = self.required(table, value, "camera", "anamorphic_squeeze")?.span();
+                return Err(
+                    self.error(
+                        span,
+                        Some(join("camera", "anamorphic_squeeze")),
+                        "anamorphic_squeeze must be positive".into()
+                    )
+                );
+            }
+            camera.anamorphic_squeeze = squeeze;
+        }
+        if let Some(value) = find(table, "stereo") {
+            camera.stereo = Some(self.stereo(value, camera.focus_dist)?);
+        }
+        let position = self.opt_vec3(table, "camera", "position")?.unwrap_or(camera.position());
+        let yaw = self.opt_f32(table, "camera", "yaw")?.unwrap_or(camera.yaw());
+        let pitch = self.opt_f32(table, "camera", "pitch")?.unwrap_or(camera.pitch());
+        camera.set_view(position, yaw, pitch);
+        Ok(camera)
+    }
+
+    fn aperture(&self, value: &Spanned<DeValue>) -> Result<Aperture, SceneError> {
+        let key = "camera.aperture";
+        let table = self.table(value, key)?;
+        let (kind, kind_span) = self.req_str(table, value, key, "type")?;
+        match kind {
+            "Circle" => {
+                self.check_keys(table, key, &["type"])?;
+                Ok(Aperture::Circle)
+            }
+            "Polygon" => {
+                self.check_keys(table, key, &["type", "blades", "rotation"])?;
+                let blades = self.opt_u16(table, key, "blades")?.unwrap_or(6);
+                if blades < 3 {
+                    let span = self.required(table, value, key, "blades")?.span();
+                    return Err(
+                        self.error(
+                            span,
+                            Some(join(key, "blades")),
+                            "an aperture needs at least 3 blades".into()
+                        )
+                    );
+                }
+                Ok(Aperture::Polygon {
+                    blades: blades as u32,
+                    rotation: self.opt_f32(table, key, "rotation")?.unwrap_or(0.0),
+                })
+            }
+            "Image" => {
+                self.check_keys(table, key, &["type", "path"])?;
+                let (path, path_span) = self.req_str(table, value, key, "path")?;
+                let path = self.resolve(path);
+                let mask = ApertureMask::load(&path).map_err(|e|
+                    self.error(
+                        path_span,
+                        Some(join(key, "path")),
+                        format!("failed to load {}: {}", path.display(), e)
+                    )
+                )?;
+                Ok(Aperture::Image(Arc::new(mask)))
+            }
+            _ =>
+                Err(
+                    self.error(
+                        kind_span,
+                        Some(join(key, "type")),
+                        format!(
+                            "unknown aperture type '{}', expected Circle, Polygon or Image",
+                            kind
+                        )
+                    )
+                ),
+        }
+    }
+
+    /// Convergence defaults to the focus distance, where the eyes would be looking.
+    fn stereo(&self, value: &Spanned<DeValue>, focus_dist: f32) -> Result<Stereo, SceneError> {
+        let key = "camera.stereo";
+        let table = self.table(value, key)?;
+        self.check_keys(table, key, &["layout", "interocular", "convergence"])?;
+        let (layout, layout_span) = self.req_str(table, value, key, "layout")?;
+        let layout = match layout {
+            "SideBySide" => StereoLayout::SideBySide,
+            "TopBottom" => StereoLayout::TopBottom,
+            _ => {
+                return Err(
+                    self.error(
+                        layout_span,
+                        Some(join(key, "layout")),
+                        format!(
+                            "unknown stereo layout '{}', expected SideBySide or TopBottom",
+                            layout
+                        )
+                    )
+                );
+            }
+        };
+        let convergence = self.opt_f32(table, key, "convergence")?.unwrap_or(focus_dist);
+        if convergence <= 0.0 {
+            let span = self.required(table, value, key, "convergence")?.span();
+            return Err(
+                self.error(
+                    span,
+                    Some(join(key, "convergence")),
+                    "convergence must be positive".into()
+                )
+            );
+        }
+        Ok(Stereo {
+            layout,
+            interocular: self.opt_f32(table, key, "interocular")?.unwrap_or(0.064),
+            convergence,
+        })
+    }
+
+    fn background(&self, value: &Spanned<DeValue>) -> Result<Background, SceneError> {
+        let key = "background";
+        let table = self.table(value, key)?;
+        let (kind, kind_span) = self.req_str(table, value, key, "type")?;
+        match kind {
+            "Sky" => {
+                self.check_keys(table, key, &["type"])?;
+                Ok(Background::Sky)
+            }
+            "Solid" => {
+                self.check_keys(table, key, &["type", "color"])?;
+                Ok(Background::Solid(self.req_vec3(table, value, key, "color")?))
+            }
+            "Environment" => {
+                self.check_keys(table, key, &["type", "path", "rotation", "intensity"])?;
+                let (path, path_span) = self.req_str(table, value, key, "path")?;
+                let path = self.resolve(path);
+                let rotation = self.opt_f32(table, key, "rotation")?.unwrap_or(0.0);
+                let intensity = self.opt_f32(table, key, "intensity")?.unwrap_or(1.0);
+                let map = EnvironmentMap::load(&path, rotation, intensity).map_err(|e|
+                    self.error(
+                        path_span,
+                        Some(join(key, "path")),
+                        format!("failed to load {}: {}", path.display(), e)
+                    )
+                )?;
+                Ok(Background::Environment(Arc::new(map)))
+            }
+            "PhysicalSky" => {
+                self.check_keys(table, key, &[
+                    "type",
+                    "sun_elevation",
+                    "sun_azimuth",
+                    "turbidity",
+                    "intensity",
+                ])?;
+                Ok(
+                    Background::PhysicalSky(
+                        PhysicalSky::new(
+                            self.opt_f32(table, key, "sun_elevation")?.unwrap_or(45.0),
+                            self.opt_f32(table, key, "sun_azimuth")?.unwrap_or(0.0),
+                            self.opt_f32(table, key, "turbidity")?.unwrap_or(3.0),
+                            self.opt_f32(table, key, "intensity")?.unwrap_or(1.0)
+                        )
+                    )
+                )
+            }
+            _ =>
+                Err(
+                    self.error(
+                        kind_span,
+                        Some(join(key, "type")),
+                        format!(
+                            "unknown background type '{}', expected one of: {}",
+                            kind,
+                            BACKGROUND_TYPES.join(", ")
+                        )
+                    )
+                ),
+        }
+    }
+
+    fn texture(&self, value: &Spanned<DeValue>, key: &str) -> Result<SharedTexture, SceneError> {
+        let table = self.table(value, key)?;
+        let (kind, kind_span) = self.req_str(table, value, key, "type")?;
+        match kind {
+            "Solid" => {
+                self.check_keys(table, key, &["type", "color"])?;
+                Ok(self.req_vec3(table, value, key, "color")?.into())
+            }
+            "Checker" => {
+                self.check_keys(table, key, &["type", "scale", "even", "odd"])?;
+                Ok(
+                    Arc::new(
+                        Checker::from_colors(
+                            self.opt_f32(table, key, "scale")?.unwrap_or(1.0),
+                            self.req_vec3(table, value, key, "even")?,
+                            self.req_vec3(table, value, key, "odd")?
+                        )
+                    )
+                )
+            }
+            "Image" => {
+                self.check_keys(table, key, &["type", "path"])?;
+                let (path, path_span) = self.req_str(table, value, key, "path")?;
+                let path = self.resolve(path);
+                let texture = ImageTexture::load(&path).map_err(|e|
+                    self.error(
+                        path_span,
+                        Some(join(key, "path")),
+                        format!("failed to load {}: {}", path.display(), e)
+                    )
+                )?;
+                Ok(Arc::new(texture))
+            }
+            _ =>
+                Err(
+                    self.error(
+                        kind_span,
+                        Some(join(key, "type")),
+                        format!("unknown texture type '{}', expected Solid, Checker or Image", kind)
+                    )
+                ),
+        }
+    }
+
+    fn material(
+        &self,
+        value: &Spanned<DeValue>,
+        key: &str,
+        textures: &[(String, SharedTexture)]
+    ) -> Result<SharedMaterial, SceneError> {
+        let table = self.table(value, key)?;
+        let (kind, kind_span) = self.req_str(table, value, key, "type")?;
+        match kind {
+            "Lambertian" => {
+                self.check_keys(table, key, &["type", "albedo"])?;
+                let albedo = self.color_ref(table, value, key, "albedo", textures)?;
+                Ok(Arc::new(Lambertian::new(albedo)))
+            }
+            "Metal" => {
+                self.check_keys(table, key, &["type", "albedo", "fuzziness"])?;
+                let albedo = self.color_ref(table, value, key, "albedo", textures)?;
+                let fuzziness = self.opt_f32(table, key, "fuzziness")?.unwrap_or(0.0);
+                Ok(Arc::new(Metal::new(albedo, fuzziness)))
+            }
+            "Dielectric" => {
+                self.check_keys(table, key, &["type", "refraction_index", "frostedness"])?;
+                Ok(
+                    Arc::new(Dielectric {
+                        refraction_index: self.req_f32(table, value, key, "refraction_index")?,
+                        frostedness: self.opt_f32(table, key, "frostedness")?.unwrap_or(0.0),
+                    })
+                )
+            }
+            "DiffuseLight" => {
+                self.check_keys(table, key, &["type", "emit"])?;
+                Ok(
+                    Arc::new(DiffuseLight {
+                        emit: self.req_vec3(table, value, key, "emit")?,
+                    })
+                )
+            }
+            "Conductor" => {
+                self.check_keys(table, key, &["type", "albedo", "roughness"])?;
+                let albedo = self.color_ref(table, value, key, "albedo", textures)?;
+                let roughness = self.opt_f32(table, key, "roughness")?.unwrap_or(0.0);
+                Ok(Arc::new(Conductor::new(albedo, roughness)))
+            }
+            "RoughDielectric" => {
+                self.check_keys(table, key, &["type", "refraction_index", "roughness"])?;
+                Ok(
+                    Arc::new(RoughDielectric {
+                        refraction_index: self.req_f32(table, value, key, "refraction_index")?,
+                        roughness: self.opt_f32(table, key, "roughness")?.unwrap_or(0.0),
+                    })
+                )
+            }
+            "Principled" => {
+                self.check_keys(table, key, &[
+                    "type",
+                    "base_color",
+                    "metallic",
+                    "roughness",
+                    "specular",
+                    "clearcoat",
+                    "clearcoat_roughness",
+                ])?;
+                let mut mat = Principled::new(
+                    self.color_ref(table, value, key, "base_color", textures)?
+                );
+                let opt = |name: &str, default: f32| {
+                    self.opt_f32(table, key, name).map(|v| v.unwrap_or(default))
+                };
+                mat.metallic = opt("metallic", mat.metallic)?;
+                mat.roughness = opt("roughness", mat.roughness)?;
+                mat.specular = opt("specular", mat.specular)?;
+                mat.clearcoat = opt("clearcoat", mat.clearcoat)?;
+                mat.clearcoat_roughness = opt("clearcoat_roughness", mat.clearcoat_roughness)?;
+                Ok(Arc::new(mat))
+            }
+            "Isotropic" => {
+                self.check_keys(table, key, &["type", "albedo"])?;
+                let albedo = self.color_ref(table, value, key, "albedo", textures)?;
+                Ok(Arc::new(Isotropic::new(albedo)))
+            }
+            "HenyeyGreenstein" => {
+                self.check_keys(table, key, &["type", "albedo", "g"])?;
+                let albedo = self.color_ref(table, value, key, "albedo", textures)?;
+                let g = self.opt_f32(table, key, "g")?.unwrap_or(0.0);
+                Ok(Arc::new(HenyeyGreenstein::new(albedo, g)))
+            }
+            _ =>
+                Err(
+                    self.error(
+                        kind_span,
+                        Some(join(key, "type")),
+                        format!(
+                            "unknown material type '{}', expected one of: {}",
+                            kind,
+                            MATERIAL_TYPES.join(", ")
+                        )
+                    )
+                ),
+        }
+    }
+
+    fn object(
+        &self,
+        value: &Spanned<DeValue>,
+        key: &str,
+        materials: &[(String, SharedMaterial)],
+        shapes: &[(String, Shape)],
+        world: &mut HittableList,
+        lights: &mut HittableList
+    ) -> Result<(), SceneError> {
+        let (object, emission) = self.shape(value, key, materials, shapes)?;
+        match emission {
+            Emission::None => world.add(object),
+            Emission::Whole => lights.add(Box::new(world.add_shared(object))),
+            Emission::Part(light) => {
+                world.add(object);
+                lights.add(Box::new(light));
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds an object, and which part of it should be sampled as a light.
+    fn shape(
+        &self,
+        value: &Spanned<DeValue>,
+        key: &str,
+        materials: &[(String, SharedMaterial)],
+        shapes: &[(String, Shape)]
+    ) -> Result<(Box<dyn Hittable>, Emission), SceneError> {
+        let table = self.table(value, key)?;
+        let (kind, kind_span) = self.req_str(table, value, key, "type")?;
+        match kind {
+            "Sphere" => {
+                self.check_keys(table, key, &["type", "center", "radius", "material"])?;
+                let center = self.req_vec3(table, value, key, "center")?;
+                let radius = self.req_f32(table, value, key, "radius")?;
+                let mat = self.material_ref(table, value, key, materials)?;
+                let emission = Emission::of(&mat);
+                Ok((Box::new(Sphere::new(center, radius, mat)), emission))
+            }
+            "MovingSphere" => {
+                self.check_keys(table, key, &[
+                    "type",
+                    "center0",
+                    "center1",
+                    "keyframes",
+                    "radius",
+                    "material",
+                ])?;
+                let keyframes = match find(table, "keyframes") {
+                    Some(value) => self.keyframes(value, &join(key, "keyframes"))?,
+                    None =>
+                        vec![
+                            (0.0, self.req_vec3(table, value, key, "center0")?),
+                            (1.0, self.req_vec3(table, value, key, "center1")?)
+                        ],
+                };
+                let radius = self.req_f32(table, value, key, "radius")?;
+                let mat = self.material_ref(table, value, key, materials)?;
+                // A light that moves has no one place to be sampled at.
+                Ok((Box::new(MovingSphere::keyframed(keyframes, radius, mat)), Emission::None))
+            }
+            "Plane" => {
+                self.check_keys(table, key, &["type", "point", "normal", "material"])?;
+                let point = self.req_vec3(table, value, key, "point")?;
+                let normal = self.req_vec3(table, value, key, "normal")?;
+                let mat = self.material_ref(table, value, key, materials)?;
+                // An infinite plane has no finite area to sample.
+                Ok((Box::new(Plane::new(point, normal, mat)), Emission::None))
+            }
+            "Quad" => {
+                self.check_keys(table, key, &["type", "corner", "u", "v", "material"])?;
+                let corner = self.req_vec3(table, value, key, "corner")?;
+                let u = self.req_vec3(table, value, key, "u")?;
+                let v = self.req_vec3(table, value, key, "v")?;
+                let mat = self.material_ref(table, value, key, materials)?;
+                let emission = Emission::of(&mat);
+                Ok((Box::new(Quad::new(corner, u, v, mat)), emission))
+            }
+            "Box" => {
+                self.check_keys(table, key, &["type", "min", "max", "material"])?;
+                let min = self.req_vec3(table, value, key, "min")?;
+                let max = self.req_vec3(table, value, key, "max")?;
+                let mat = self.material_ref(table, value, key, materials)?;
+                let emission = Emission::of(&mat);
+                Ok((Box::new(axis_aligned_box(min, max, mat)), emission))
+            }
+            "Disk" => {
+                self.check_keys(table, key, &["type", "center", "normal", "radius", "material"])?;
+                let center = self.req_vec3(table, value, key, "center")?;
+                let normal = self.req_vec3(table, value, key, "normal")?;
+                let radius = self.req_f32(table, value, key, "radius")?;
+                let mat = self.material_ref(table, value, key, materials)?;
+                let emission = Emission::of(&mat);
+                Ok((Box::new(Disk::new(center, normal, radius, mat)), emission))
+            }
+            "Mesh" => {
+                self.check_keys(table, key, &["type", "path", "material"])?;
+                let (path, path_span) = self.req_str(table, value, key, "path")?;
+                let default_mat: SharedMaterial = if find(table, "material").is_some() {
+                    self.material_ref(table, value, key, materials)?
+                } else {
+                    Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)))
+                };
+                let path = self.resolve(path);
+                let mesh = Mesh::load_obj(&path, default_mat).map_err(|e|
+                    self.error(
+                        path_span,
+                        Some(join(key, "path")),
+                        format!("failed to load {}: {}", path.display(), e)
+                    )
+                )?;
+                let emission = mesh.emitters().map_or(Emission::None, Emission::Part);
+                Ok((Box::new(mesh), emission))
+            }
+            "Instance" => {
+                self.check_keys(table, key, &["type", "shape", "translate", "rotate", "scale"])?;
+                let (object, emission) = self.shape_ref(
+                    table,
+                    value,
+                    key,
+                    "shape",
+                    materials,
+                    shapes
+                )?;
+                let transform = self.transform(table, key)?;
+                let emission = match emission {
+                    Emission::Part(light) =>
+                        Emission::Part(Arc::new(Instance::new(light, transform))),
+                    emission => emission,
+                };
+                Ok((Box::new(Instance::new(object, transform)), emission))
+            }
+            "ConstantMedium" => {
+                self.check_keys(table, key, &["type", "boundary", "density", "material"])?;
+                let (boundary, _) = self.shape_ref(
+                    table,
+                    value,
+                    key,
+                    "boundary",
+                    materials,
+                    shapes
+                )?;
+                let density = self.density(table, value, key)?;
+                let mat = self.material_ref(table, value, key, materials)?;
+                Ok((Box::new(ConstantMedium::new(boundary, density, mat)), Emission::None))
+            }
+            _ =>
+                Err(
+                    self.error(
+                        kind_span,
+                        Some(join(key, "type")),
+                        format!(
+                            "unknown object type '{}', expected one of: {}",
+                            kind,
+                            OBJECT_TYPES.join(", ")
+                        )
+                    )
+                ),
+        }
+    }
+
+    /// A shape given either by its name in `[shapes]` or as an inline table.
+    fn shape_ref(
+        &self,
+        table: &DeTable,
+        parent: &Spanned<DeValue>,
+        key: &str,
+        name: &str,
+        materials: &[(String, SharedMaterial)],
+        shapes: &[(String, Shape)]
+    ) -> Result<(Arc<dyn Hittable>, Emission), SceneError> {
+        let value = self.required(table, parent, key, name)?;
+        let DeValue::String(shape_name) = value.get_ref() else {
+            let (object, emission) = self.shape(value, &join(key, name), materials, shapes)?;
+            return Ok((Arc::from(object), emission));
+        };
+        shapes
+            .iter()
+            .find(|(n, _)| n == shape_name.as_ref())
+            .map(|(_, shape)| (shape.object.clone(), shape.emission.clone()))
+            .ok_or_else(||
+                self.error(
+                    value.span(),
+                    Some(join(key, name)),
+                    format!("undefined shape '{}'", shape_name)
+                )
+            )
+    }
+
+    fn atmosphere(
+        &self,
+        value: &Spanned<DeValue>,
+        materials: &[(String, SharedMaterial)]
+    ) -> Result<Atmosphere, SceneError> {
+        let key = "atmosphere";
+        let table = self.table(value, key)?;
+        self.check_keys(table, key, &["density", "extent", "material"])?;
+        let density = self.density(table, value, key)?;
+        let extent = self.opt_f32(table, key, "extent")?.unwrap_or(100.0);
+        if extent <= 0.0 {
+            let span = self.required(table, value, key, "extent")?.span();
+            return Err(
+                self.error(span, Some(join(key, "extent")), "extent must be positive".into())
+            );
+        }
+        let mat = self.material_ref(table, value, key, materials)?;
+        Ok(Atmosphere::new(density, extent, mat))
+    }
+
+    fn density(
+        &self,
+        table: &DeTable,
+        parent: &Spanned<DeValue>,
+        key: &str
+    ) -> Result<f32, SceneError> {
+        let density = self.req_f32(table, parent, key, "density")?;
+        if density <= 0.0 {
+            let span = self.required(table, parent, key, "density")?.span();
+            return Err(
+                self.error(span, Some(join(key, "density")), "density must be positive".into())
+            );
+        }
+        Ok(density)
+    }
+
+    /// Applies `scale`, then `rotate` (degrees about x, then y, then z), then `translate`.
+    fn transform(&self, table: &DeTable, key: &str) -> Result<Mat4, SceneError> {
+        let scale = match find(table, "scale") {
+            None => Vec3::new(1.0, 1.0, 1.0),
+            Some(value) => {
+                let scale_key = join(key, "scale");
+                let scale = match value.get_ref() {
+                    DeValue::Array(_) => self.vec3(value, &scale_key)?,
+                    DeValue::Float(_) | DeValue::Integer(_) => {
+                        let s = self.number(value, &scale_key)?;
+                        Vec3::new(s, s, s)
+                    }
+                    _ => {
+                        return Err(
+                            self.type_error(value, &scale_key, "a number or an array of 3 numbers")
+                        );
+                    }
+                };
+                if scale.x() == 0.0 || scale.y() == 0.0 || scale.z() == 0.0 {
+                    return Err(
+                        self.error(value.span(), Some(scale_key), "scale must not be zero".into())
+                    );
+                }
+                scale
+            }
+        };
+        let zero = Vec3::new(0.0, 0.0, 0.0);
+        let rotate = self.opt_vec3(table, key, "rotate")?.unwrap_or(zero);
+        let translate = self.opt_vec3(table, key, "translate")?.unwrap_or(zero);
+        Ok(Mat4::translation(translate) * Mat4::rotation_euler(rotate) * Mat4::scaling(scale))
+    }
+
+    /// An array of `{ time, center }` tables, in any order.
+    fn keyframes(
+        &self,
+        value: &Spanned<DeValue>,
+        key: &str
+    ) -> Result<Vec<(f32, Point3)>, SceneError> {
+        let DeValue::Array(array) = value.get_ref() else {
+            return Err(self.type_error(value, key, "an array of tables"));
+        };
+        if array.is_empty() {
+            return Err(self.type_error(value, key, "a non-empty array of tables"));
+        }
+        array
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let frame_key = format!("{}[{}]", key, i);
+                let table = self.table(frame, &frame_key)?;
+                self.check_keys(table, &frame_key, &["time", "center"])?;
+                Ok((
+                    self.req_f32(table, frame, &frame_key, "time")?,
+                    self.req_vec3(table, frame, &frame_key, "center")?,
+                ))
+            })
+            .collect()
+    }
+
+    /// A colour is either a literal or the name of an entry in `[textures]`.
+    fn color_ref(
+        &self,
+        table: &DeTable,
+        parent: &Spanned<DeValue>,
+        key: &str,
+        name: &str,
+        textures: &[(String, SharedTexture)]
+    ) -> Result<SharedTexture, SceneError> {
+        let value = self.required(table, parent, key, name)?;
+        let DeValue::String(texture_name) = value.get_ref() else {
+            return Ok(self.vec3(value, &join(key, name))?.into());
+        };
+        textures
+            .iter()
+            .find(|(n, _)| n == texture_name.as_ref())
+            .map(|(_, texture)| texture.clone())
+            .ok_or_else(||
+                self.error(
+                    value.span(),
+                    Some(join(key, name)),
+                    format!("undefined texture '{}'", texture_name)
+                )
+            )
+    }
+
+    fn material_ref(
+        &self,
+        table: &DeTable,
+        parent: &Spanned<DeValue>,
+        key: &str,
+        materials: &[(String, SharedMaterial)]
+    ) -> Result<SharedMaterial, SceneError> {
+        let (name, span) = self.req_str(table, parent, key, "material")?;
+        materials
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, mat)| mat.clone())
+            .ok_or_else(||
+                self.error(
+                    span,
+                    Some(join(key, "material")),
+                    format!("undefined material '{}'", name)
+                )
+            )
+    }
+
+    /// Paths in a scene file are relative to the file itself.
+    fn resolve(&self, path: &str) -> PathBuf {
+        self.path.parent().unwrap_or(Path::new("")).join(path)
+    }
+
+    fn table<'v, 'i>(
+        &self,
+        value: &'v Spanned<DeValue<'i>>,
+        key: &str
+    ) -> Result<&'v DeTable<'i>, SceneError> {
+        match value.get_ref() {
+            DeValue::Table(table) => Ok(table),
+            _ => Err(self.type_error(value, key, "a table")),
+        }
+    }
+
+    fn check_keys(
+        &self,
+        table: &DeTable,
+        prefix: &str,
+        allowed: &[&str]
+    ) -> Result<(), SceneError> {
+        for (name, _) in table.iter() {
+            if !allowed.contains(&name.get_ref().as_ref()) {
+                return Err(
+                    self.error(
+                        name.span(),
+                        Some(join(prefix, name.get_ref())),
+                        format!("unknown key, expected one of: {}", allowed.join(", "))
+                    )
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn required<'v, 'i>(
+        &self,
+        table: &'v DeTable<'i>,
+        parent: &Spanned<DeValue>,
+        prefix: &str,
+        name: &str
+    ) -> Result<&'v Spanned<DeValue<'i>>, SceneError> {
+        find(table, name).ok_or_else(||
+            self.error(parent.span(), Some(join(prefix, name)), "missing required key".to_string())
+        )
+    }
+
+    fn req_str<'v>(
+        &self,
+        table: &'v DeTable,
+        parent: &Spanned<DeValue>,
+        prefix: &str,
+        name: &str
+    ) -> Result<(&'v str, Range<usize>), SceneError> {
+        let value = self.required(table, parent, prefix, name)?;
+        match value.get_ref() {
+            DeValue::String(s) => Ok((s.as_ref(), value.span())),
+            _ => Err(self.type_error(value, &join(prefix, name), "a string")),
+        }
+    }
+
+    fn req_f32(
+        &self,
+        table: &DeTable,
+        parent: &Spanned<DeValue>,
+        prefix: &str,
+        name: &str
+    ) -> Result<f32, SceneError> {
+        let value = self.required(table, parent, prefix, name)?;
+        self.number(value, &join(prefix, name))
+    }
+
+    fn req_vec3(
+        &self,
+        table: &DeTable,
+        parent: &Spanned<DeValue>,
+        prefix: &str,
+        name: &str
+    ) -> Result<Vec3, SceneError> {
+        let value = self.required(table, parent, prefix, name)?;
+        self.vec3(value, &join(prefix, name))
+    }
+
+    fn opt_f32(
+        &self,
+        table: &DeTable,
+        prefix: &str,
+        name: &str
+    ) -> Result<Option<f32>, SceneError> {
+        find(table, name)
+            .map(|value| self.number(value, &join(prefix, name)))
+            .transpose()
+    }
+
+    fn opt_vec3(
+        &self,
+        table: &DeTable,
+        prefix: &str,
+        name: &str
+    ) -> Result<Option<Vec3>, SceneError> {
+        find(table, name)
+            .map(|value| self.vec3(value, &join(prefix, name)))
+            .transpose()
+    }
+
+    fn opt_u16(
+        &self,
+        table: &DeTable,
+        prefix: &str,
+        name: &str
+    ) -> Result<Option<u16>, SceneError> {
+        let value = self.opt_integer(table, prefix, name, u16::MAX as u64)?;
+        Ok(value.map(|v| v as u16))
+    }
+
+    fn opt_u64(
+        &self,
+        table: &DeTable,
+        prefix: &str,
+        name: &str
+    ) -> Result<Option<u64>, SceneError> {
+        self.opt_integer(table, prefix, name, u64::MAX)
+    }
+
+    fn opt_integer(
+        &self,
+        table: &DeTable,
+        prefix: &str,
+        name: &str,
+        max: u64
+    ) -> Result<Option<u64>, SceneError> {
+        let Some(value) = find(table, name) else {
+            return Ok(None);
+        };
+        let key = join(prefix, name);
+        let DeValue::Integer(int) = value.get_ref() else {
+            return Err(self.type_error(value, &key, "an integer"));
+        };
+        u64::from_str_radix(&int.as_str().replace('_', ""), int.radix())
+            .ok()
+            .filter(|v| *v <= max)
+            .map(Some)
+            .ok_or_else(||
+                self.error(
+                    value.span(),
+                    Some(key),
+                    format!("expected an integer between 0 and {}", max)
+                )
+            )
+    }
+
+    fn number(&self, value: &Spanned<DeValue>, key: &str) -> Result<f32, SceneError> {
+        let parsed = match value.get_ref() {
+            DeValue::Float(float) => float.as_str().replace('_', "").parse::<f32>().ok(),
+            DeValue::Integer(int) =>
+                i64
+                    ::from_str_radix(&int.as_str().replace('_', ""), int.radix())
+                    .ok()
+                    .map(|i| i as f32),
+            _ => None,
+        };
+        parsed.ok_or_else(|| self.type_error(value, key, "a number"))
+    }
+
+    fn vec3(&self, value: &Spanned<DeValue>, key: &str) -> Result<Vec3, SceneError> {
+        let DeValue::Array(array) = value.get_ref() else {
+            return Err(self.type_error(value, key, "an array of 3 numbers"));
+        };
+        if array.len() != 3 {
+            return Err(self.type_error(value, key, "an array of 3 numbers"));
+        }
+        Ok(
+            Vec3::new(
+                self.number(&array[0], key)?,
+                self.number(&array[1], key)?,
+                self.number(&array[2], key)?
+            )
+        )
+    }
+
+    fn type_error(&self, value: &Spanned<DeValue>, key: &str, expected: &str) -> SceneError {
+        self.error(
+            value.span(),
+            Some(key.to_string()),
+            format!("expected {}, found {}", expected, value.get_ref().type_str())
+        )
+    }
+
+    fn error(&self, span: Range<usize>, key: Option<String>, message: String) -> SceneError {
+        let offset = span.start.min(self.src.len());
+        SceneError {
+            file: self.path.to_path_buf(),
+            line: self.src[..offset].matches('\n').count() + 1,
+            key,
+            message,
+        }
+    }
+}
+
+fn find<'v, 'i>(table: &'v DeTable<'i>, name: &str) -> Option<&'v Spanned<DeValue<'i>>> {
+    table
+        .iter()
+        .find(|(key, _)| key.get_ref() == name)
+        .map(|(_, value)| value)
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() { name.to_string() } else { format!("{}.{}", prefix, name) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(src: &str) -> Result<Scene, SceneError> {
+        Loader { path: Path::new("test.toml"), src }.load(Size { w: 8, h: 8 })
+    }
+
+    #[test]
+    fn errors_report_line_and_key() {
+        let src = concat!(
+            "[camera]\n",
+            "fov = 40\n",
+            "\n",
+            "[materials.red]\n",
+            "type = \"Lambertian\"\n",
+            "albedo = [1.0, \"zero\", 0.0]\n"
+        );
+        let err = load_str(src).err().unwrap();
+        assert_eq!(err.line, 6);
+        assert_eq!(err.key.as_deref(), Some("materials.red.albedo"));
+        assert_eq!(err.to_string().split(": ").next(), Some("test.toml:6"));
+
+        let err = load_str("[camera]\nfov = 40\nfov = 50\n").err().unwrap();
+        assert_eq!(err.line, 3);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{ PI, onb::Onb, random_f32, rng::Rng, vec3::{ Color, Vec3, dot } };
+
+/// Angular radius of the sun's disc, in radians.
+const SUN_RADIUS: f32 = 0.00465;
+/// Brings the model's kcd/m² down to values in the range of the other backgrounds.
+const SKY_SCALE: f32 = 0.02;
+/// Radiance of the sun above the atmosphere, on the same scale as the sky.
+const SUN_RADIANCE: f32 = 80000.0;
+
+/// Clear daylight sky after Preetham, Shirley and Smits (1999), with the sun as a
+/// small bright disc that is sampled as a light. Angles are in degrees: elevation
+/// above the horizon, azimuth clockwise from -z seen from above.
+#[derive(Debug, Clone)]
+pub struct PhysicalSky {
+    sun_elevation: f32,
+    sun_azimuth: f32,
+    turbidity: f32,
+    intensity: f32,
+    sun_direction: Vec3,
+    sun_color: Color,
+    /// Perez coefficients for luminance and the two chromaticities.
+    perez: [[f32; 5]; 3],
+    /// Zenith value of each channel divided by the Perez function at the zenith.
+    zenith: [f32; 3],
+}
+
+impl PhysicalSky {
+    /// `turbidity` is the haziness, from 2 for a very clear sky to about 10.
+    pub fn new(sun_elevation: f32, sun_azimuth: f32, turbidity: f32, intensity: f32) -> Self {
+        let mut sky = PhysicalSky {
+            sun_elevation,
+            sun_azimuth,
+            turbidity: turbidity.clamp(1.7, 10.0),
+            intensity,
+            sun_direction: Vec3::new(0.0, 1.0, 0.0),
+            sun_color: Color::new(0.0, 0.0, 0.0),
+            perez: [[0.0; 5]; 3],
+            zenith: [0.0; 3],
+        };
+        sky.set_sun(sun_elevation, sun_azimuth);
+        sky
+    }
+
+    pub fn sun_elevation(&self) -> f32 {
+        self.sun_elevation
+    }
+
+    pub fn sun_azimuth(&self) -> f32 {
+        self.sun_azimuth
+    }
+
+    pub fn turbidity(&self) -> f32 {
+        self.turbidity
+    }
+
+    pub fn set_sun(&mut self, elevation: f32, azimuth: f32) {
+        self.sun_elevation = elevation.clamp(-90.0, 90.0);
+        self.sun_azimuth = azimuth.rem_euclid(360.0);
+        let (sin_e, cos_e) = self.sun_elevation.to_radians().sin_cos();
+        let (sin_a, cos_a) = self.sun_azimuth.to_radians().sin_cos();
+        self.sun_direction = Vec3::new(cos_e * sin_a, sin_e, -cos_e * cos_a);
+
+        // The model only covers a sun above the horizon; below it the sky stays at dusk.
+        let t = self.turbidity;
+        let theta_s = (90.0 - self.sun_elevation.max(0.0)).to_radians();
+        self.perez = [
+            [
+                0.1787 * t - 1.463,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.067 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.095 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = ((4.0453 * t - 4.971) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let (th, th2, th3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+        let t2 = t * t;
+        let zenith_x =
+            t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th) +
+            t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394) +
+            (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let zenith_yc =
+            t2 * (0.00275 * th3 - 0.0061 * th2 + 0.00317 * th) +
+            t * (-0.04214 * th3 + 0.0897 * th2 - 0.04153 * th + 0.00516) +
+            (0.15346 * th3 - 0.26756 * th2 + 0.0667 * th + 0.26688);
+        let zenith = [zenith_y, zenith_x, zenith_yc];
+        for (channel, value) in zenith.into_iter().enumerate() {
+            self.zenith[channel] = value / perez(&self.perez[channel], 1.0, theta_s);
+        }
+
+        self.sun_color = if self.sun_elevation > 0.0 {
+            SUN_RADIANCE * sun_transmittance(theta_s, t)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+    }
+
+    pub fn color(&self, direction: Vec3) -> Color {
+        let d = direction.to_unit_vector();
+        let mut color = self.sky_color(d);
+        if dot(&d, &self.sun_direction) >= self.sun_cos_max() {
+            color = color + self.sun_color;
+        }
+        self.intensity * color
+    }
+
+    /// Density of `random` generating `direction`: uniform over the sun's disc.
+    pub fn pdf_value(&self, direction: Vec3) -> f32 {
+        let d = direction.to_unit_vector();
+        if self.sun_elevation <= 0.0 || dot(&d, &self.sun_direction) < self.sun_cos_max() {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * sun_one_minus_cos())
+    }
+
+    pub fn random(&self, rng: &mut Rng) -> Vec3 {
+        let z = 1.0 - random_f32(rng) * sun_one_minus_cos();
+        let phi = 2.0 * PI * random_f32(rng);
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z);
+        Onb::new(self.sun_direction).transform(local)
+    }
+
+    fn sun_cos_max(&self) -> f32 {
+        1.0 - sun_one_minus_cos()
+    }
+
+    fn sky_color(&self, d: Vec3) -> Color {
+        // Below the horizon the horizon's colour carries on.
+        let cos_theta = d.y().max(0.01);
+        let gamma = dot(&d, &self.sun_direction).clamp(-1.0, 1.0).acos();
+        let value = |channel: usize| {
+            self.zenith[channel] * perez(&self.perez[channel], cos_theta, gamma)
+        };
+        let (luminance, x, y) = (SKY_SCALE * value(0), value(1), value(2));
+        if y <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let cie_x = (x / y) * luminance;
+        let cie_z = ((1.0 - x - y) / y) * luminance;
+        Color::new(
+            (3.2406 * cie_x - 1.5372 * luminance - 0.4986 * cie_z).max(0.0),
+            (-0.9689 * cie_x + 1.8758 * luminance + 0.0415 * cie_z).max(0.0),
+            (0.0557 * cie_x - 0.204 * luminance + 1.057 * cie_z).max(0.0)
+        )
+    }
+}
+
+/// Perez et al.'s sky luminance distribution, `theta` from the zenith and `gamma`
+/// from the sun.
+fn perez(c: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    let cos_gamma = gamma.cos();
+    (1.0 + c[0] * (c[1] / cos_theta.max(0.01)).exp()) *
+        (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+/// 1 - cos of the sun's angular radius, written so it keeps its precision in f32.
+fn sun_one_minus_cos() -> f32 {
+    2.0 * (SUN_RADIUS / 2.0).sin().powi(2)
+}
+
+/// Share of sunlight that reaches the ground through Rayleigh and aerosol scattering,
+/// at wavelengths standing in for red, green and blue (Preetham's appendix).
+fn sun_transmittance(theta_s: f32, turbidity: f32) -> Color {
+    let zenith_deg = theta_s.to_degrees();
+    let air_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - zenith_deg).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let channel = |micrometres: f32| {
+        let rayleigh = 0.008735 * micrometres.powf(-4.08);
+        let aerosol = beta * micrometres.powf(-1.3);
+        (-air_mass * (rayleigh + aerosol)).exp()
+    };
+    Color::new(channel(0.65), channel(0.57), channel(0.475))
+}
@@ -1,48 +1,204 @@
+use std::f32::consts::PI;
 use std::sync::Arc;
 
 use crate::{
-    hittable::{ HitRecord, Hittable },
+    aabb::Aabb,
+    hittable::{ HitRecord, Hittable, MaterialIds },
     interval::Interval,
     material::Material,
+    onb::Onb,
+    random_f32,
     ray::Ray,
-    vec3::{ Point3, dot },
+    rng::Rng,
+    vec3::{ Point3, Vec3, dot },
 };
 
 pub struct Sphere {
     center: Point3,
     radius: f32,
     mat: Arc<dyn Material + Send + Sync>,
+    bbox: Aabb,
+    object_id: u32,
+    material_id: u32,
 }
 
 impl Sphere {
     pub fn new(center: Point3, radius: f32, mat: Arc<dyn Material + Send + Sync>) -> Self {
-        Sphere { center, radius: radius.max(0.0), mat }
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        let bbox = Aabb::from_points(center - rvec, center + rvec);
+        Sphere { center, radius, mat, bbox, object_id: 0, material_id: 0 }
     }
 }
 
-impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let oc = self.center - ray.origin();
-        let a = ray.direction().length_squared();
-        let h = dot(&ray.direction(), &oc);
-        let c = oc.length_squared() - self.radius * self.radius;
-        let discriminant = h * h - a * c;
-        if discriminant < 0.0 {
+impl Sphere {
+    /// Maps a point on the unit sphere to (u, v), with u going around the y axis
+    /// starting from -x and v going from -y to +y.
+    fn get_sphere_uv(p: Point3) -> (f32, f32) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+}
+
+fn hit_sphere<'a>(
+    center: Point3,
+    radius: f32,
+    mat: &'a Arc<dyn Material + Send + Sync>,
+    ray: &Ray,
+    ray_t: Interval
+) -> Option<HitRecord<'a>> {
+    let oc = center - ray.origin();
+    let a = ray.direction().length_squared();
+    let h = dot(&ray.direction(), &oc);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = h * h - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+    let mut root: f32 = (h - sqrtd) / a;
+
+    if !ray_t.surrounds(root) {
+        root = (h + sqrtd) / a;
+        if !ray_t.surrounds(root) {
             return None;
         }
-        let sqrtd = discriminant.sqrt();
-        let mut root: f32 = (h - sqrtd) / a;
+    }
 
-        if !ray_t.surrounds(root) {
-            root = (h + sqrtd) / a;
-            if !ray_t.surrounds(root) {
-                return None;
-            }
+    let t = root;
+    // For large spheres `c` above loses most of its f32 precision, which leaves
+    // `ray.at(t)` off the surface; snap it back so shadow rays don't self-intersect.
+    let offset = ray.at(t) - center;
+    let p = center + offset * (radius / offset.length());
+    let outward_normal = (p - center) / radius;
+    let mut rec = HitRecord::new(p, t, ray, mat, outward_normal);
+    (rec.u, rec.v) = Sphere::get_sphere_uv(outward_normal);
+    Some(rec)
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord<'_>> {
+        let mut rec = hit_sphere(self.center, self.radius, &self.mat, ray, ray_t)?;
+        rec.object_id = self.object_id;
+        rec.material_id = self.material_id;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 0.0;
+        }
+        let ray = Ray::new(origin, direction);
+        let t = Interval::new(0.001, f32::INFINITY);
+        if hit_sphere(self.center, self.radius, &self.mat, &ray, t).is_none() {
+            return 0.0;
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    /// Samples the cone of directions subtended by the sphere, seen from `origin`.
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return Vec3::random_unit_vector(rng);
         }
 
-        let t = root;
-        let p = ray.at(t);
-        let outward_normal = (p - self.center) / self.radius;
-        Some(HitRecord::new(p, t, &ray, &self.mat, outward_normal))
+        let r1 = random_f32(rng);
+        let r2 = random_f32(rng);
+        let z = 1.0 + r2 * ((1.0 - radius_squared / distance_squared).sqrt() - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z);
+        Onb::new(direction).transform(local)
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.object_id = object_id;
+        self.material_id = material_ids.id(&self.mat);
+    }
+}
+
+/// Sphere whose centre moves through keyframes, in a straight line between each pair
+/// and standing still before the first and after the last.
+pub struct MovingSphere {
+    /// (time, centre) pairs, sorted by time.
+    keyframes: Vec<(f32, Point3)>,
+    radius: f32,
+    mat: Arc<dyn Material + Send + Sync>,
+    bbox: Aabb,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl MovingSphere {
+    /// Moves from `center0` at time 0 to `center1` at time 1.
+    pub fn linear(
+        center0: Point3,
+        center1: Point3,
+        radius: f32,
+        mat: Arc<dyn Material + Send + Sync>
+    ) -> Self {
+        MovingSphere::keyframed(vec![(0.0, center0), (1.0, center1)], radius, mat)
+    }
+
+    /// Panics if `keyframes` is empty.
+    pub fn keyframed(
+        mut keyframes: Vec<(f32, Point3)>,
+        radius: f32,
+        mat: Arc<dyn Material + Send + Sync>
+    ) -> Self {
+        assert!(!keyframes.is_empty(), "a moving sphere needs at least one keyframe");
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(radius, radius, radius);
+        // Between keyframes the sphere stays inside the boxes around its ends.
+        let bbox = keyframes.iter().fold(Aabb::empty(), |bbox, &(_, center)| {
+            Aabb::surrounding(&bbox, &Aabb::from_points(center - rvec, center + rvec))
+        });
+        MovingSphere { keyframes, radius, mat, bbox, object_id: 0, material_id: 0 }
+    }
+
+    pub fn center(&self, time: f32) -> Point3 {
+        let next = self.keyframes.partition_point(|&(t, _)| t <= time);
+        if next == 0 {
+            return self.keyframes[0].1;
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].1;
+        }
+        let (t0, c0) = self.keyframes[next - 1];
+        let (t1, c1) = self.keyframes[next];
+        c0 + ((time - t0) / (t1 - t0)) * (c1 - c0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord<'_>> {
+        let center = self.center(ray.time());
+        let mut rec = hit_sphere(center, self.radius, &self.mat, ray, ray_t)?;
+        rec.object_id = self.object_id;
+        rec.material_id = self.material_id;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.object_id = object_id;
+        self.material_id = material_ids.id(&self.mat);
     }
 }
@@ -0,0 +1,94 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use image::ImageResult;
+
+use crate::{ interval::Interval, vec3::{ Color, Point3 } };
+
+pub trait Texture {
+    fn value(&self, u: f32, v: f32, p: &Point3) -> Color;
+}
+
+pub type SharedTexture = Arc<dyn Texture + Send + Sync>;
+
+pub struct SolidColor {
+    pub albedo: Color,
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _p: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+impl From<Color> for SharedTexture {
+    fn from(albedo: Color) -> Self {
+        Arc::new(SolidColor { albedo })
+    }
+}
+
+/// Alternates between two textures on a 3D grid, so it needs no UVs.
+pub struct Checker {
+    pub inv_scale: f32,
+    pub even: SharedTexture,
+    pub odd: SharedTexture,
+}
+
+impl Checker {
+    pub fn new(scale: f32, even: SharedTexture, odd: SharedTexture) -> Self {
+        Checker { inv_scale: 1.0 / scale, even, odd }
+    }
+
+    pub fn from_colors(scale: f32, even: Color, odd: Color) -> Self {
+        Self::new(scale, even.into(), odd.into())
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f32, v: f32, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x()).floor() as i32;
+        let y = (self.inv_scale * p.y()).floor() as i32;
+        let z = (self.inv_scale * p.z()).floor() as i32;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn load(path: &Path) -> ImageResult<Self> {
+        let img = image::open(path)?.into_rgb32f();
+        let (width, height) = (img.width() as usize, img.height() as usize);
+        // Image files are gamma encoded, albedo is linear.
+        let pixels = img
+            .pixels()
+            .map(|px| Color::new(px[0].powi(2), px[1].powi(2), px[2].powi(2)))
+            .collect();
+        Ok(ImageTexture { width, height, pixels })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: &Point3) -> Color {
+        if self.pixels.is_empty() {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        let unit = Interval::new(0.0, 1.0);
+        let u = unit.clamp(u);
+        let v = 1.0 - unit.clamp(v);
+
+        let i = ((u * (self.width as f32)) as usize).min(self.width - 1);
+        let j = ((v * (self.height as f32)) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i]
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{ HitRecord, Hittable, MaterialIds },
+    interval::Interval,
+    material::Material,
+    random_f32,
+    ray::Ray,
+    rng::Rng,
+    vec3::{ Point3, Vec3, cross, dot },
+};
+
+#[derive(Clone)]
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: [(f32, f32); 3],
+    mat: Arc<dyn Material + Send + Sync>,
+    bbox: Aabb,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Point3; 3], mat: Arc<dyn Material + Send + Sync>) -> Self {
+        let bbox = Aabb::surrounding(
+            &Aabb::from_points(vertices[0], vertices[1]),
+            &Aabb::from_points(vertices[2], vertices[2])
+        );
+        Triangle {
+            vertices,
+            normals: None,
+            uvs: [
+                (0.0, 0.0),
+                (1.0, 0.0),
+                (0.0, 1.0),
+            ],
+            mat,
+            bbox,
+            object_id: 0,
+            material_id: 0,
+        }
+    }
+
+    /// Per-vertex normals are interpolated across the face for smooth shading.
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals.map(|n| n.to_unit_vector()));
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f32, f32); 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn area(&self) -> f32 {
+        let [v0, v1, v2] = self.vertices;
+        0.5 * cross(&(v1 - v0), &(v2 - v0)).length()
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.mat.is_emissive()
+    }
+
+    fn intersect(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // Möller–Trumbore intersection.
+        let [v0, v1, v2] = self.vertices;
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let pvec = cross(&ray.direction(), &edge2);
+        let det = dot(&edge1, &pvec);
+        if det.abs() < 1e-8 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = ray.origin() - v0;
+        let b1 = dot(&tvec, &pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let qvec = cross(&tvec, &edge1);
+        let b2 = dot(&ray.direction(), &qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = dot(&edge2, &qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        let p = ray.at(t);
+        let geometric_normal = cross(&edge1, &edge2).to_unit_vector();
+        let mut rec = HitRecord::new(p, t, ray, &self.mat, geometric_normal);
+
+        if let Some([n0, n1, n2]) = self.normals {
+            let shading_normal = (b0 * n0 + b1 * n1 + b2 * n2).to_unit_vector();
+            rec.normal = if rec.front_face { shading_normal } else { -shading_normal };
+        }
+
+        let [uv0, uv1, uv2] = self.uvs;
+        rec.u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+        rec.v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+        rec.object_id = self.object_id;
+        rec.material_id = self.material_id;
+        Some(rec)
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord<'_>> {
+        self.intersect(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let ray = Ray::new(origin, direction);
+        let Some(rec) = self.intersect(&ray, Interval::new(0.001, f32::INFINITY)) else {
+            return 0.0;
+        };
+        let [v0, v1, v2] = self.vertices;
+        let normal = cross(&(v1 - v0), &(v2 - v0)).to_unit_vector();
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (dot(&direction, &normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let [v0, v1, v2] = self.vertices;
+        let (mut s, mut t) = (random_f32(rng), random_f32(rng));
+        // Points past the diagonal fold back into the triangle, keeping them uniform.
+        if s + t > 1.0 {
+            (s, t) = (1.0 - s, 1.0 - t);
+        }
+        v0 + s * (v1 - v0) + t * (v2 - v0) - origin
+    }
+
+    fn assign_ids(&mut self, object_id: u32, material_ids: &mut MaterialIds) {
+        self.object_id = object_id;
+        self.material_id = material_ids.id(&self.mat);
+    }
+}
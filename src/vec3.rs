@@ -1,6 +1,6 @@
 use std::ops::{ Neg, Sub, Add, Mul, Div };
 
-use crate::{ interval::Interval, random_f32, random_f32_range };
+use crate::{ interval::Interval, random_f32, random_f32_range, rng::Rng };
 
 #[derive(Debug, Clone, Copy)]
 pub struct Vec3(f32, f32, f32);
@@ -12,17 +12,21 @@ impl Vec3 {
         Self(x, y, z)
     }
 
-    pub fn random() -> Self {
-        Self(random_f32(), random_f32(), random_f32())
+    pub fn random(rng: &mut Rng) -> Self {
+        Self(random_f32(rng), random_f32(rng), random_f32(rng))
     }
 
-    pub fn random_range(min: f32, max: f32) -> Self {
-        Self(random_f32_range(min, max), random_f32_range(min, max), random_f32_range(min, max))
+    pub fn random_range(rng: &mut Rng, min: f32, max: f32) -> Self {
+        Self(
+            random_f32_range(rng, min, max),
+            random_f32_range(rng, min, max),
+            random_f32_range(rng, min, max)
+        )
     }
 
-    pub fn random_unit_vector() -> Self {
+    pub fn random_unit_vector(rng: &mut Rng) -> Self {
         loop {
-            let vec = Self::random_range(-1.0, 1.0);
+            let vec = Self::random_range(rng, -1.0, 1.0);
             let lensq = vec.length_squared();
             if 1e-160 < lensq && lensq <= 1.0 {
                 return vec / lensq.sqrt();
@@ -30,17 +34,17 @@ impl Vec3 {
         }
     }
 
-    pub fn random_on_hemisphere(normal: Self) -> Self {
-        let on_unit_sphere = Self::random_unit_vector();
+    pub fn random_on_hemisphere(rng: &mut Rng, normal: Self) -> Self {
+        let on_unit_sphere = Self::random_unit_vector(rng);
         if dot(&on_unit_sphere, &normal) > 0.0 {
             return on_unit_sphere;
         }
         -on_unit_sphere
     }
 
-    pub fn random_in_unit_disk() -> Self {
+    pub fn random_in_unit_disk(rng: &mut Rng) -> Self {
         loop {
-            let p = Self(random_f32_range(-1.0, 1.0), random_f32_range(-1.0, 1.0), 0.0);
+            let p = Self(random_f32_range(rng, -1.0, 1.0), random_f32_range(rng, -1.0, 1.0), 0.0);
             if p.length_squared() < 1.0 {
                 return p;
             }
@@ -154,11 +158,31 @@ impl Div<f32> for Vec3 {
 }
 
 impl Color {
+    /// Rec. 709 relative luminance.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x() + 0.7152 * self.y() + 0.0722 * self.z()
+    }
+
+    /// Gamma 2 encoding clamped just below 1, shared by the display and every saved format.
+    pub fn gamma_encoded(&self) -> Color {
+        let intensity = Interval::new(0.0, 0.999);
+        Color::new(
+            intensity.clamp(self.x().sqrt()),
+            intensity.clamp(self.y().sqrt()),
+            intensity.clamp(self.z().sqrt())
+        )
+    }
+
     pub fn to_u32(&self) -> u32 {
+        self.gamma_encoded().pack_u32()
+    }
+
+    /// Packs values that are already display ready, clamped to [0, 1), as 0xRRGGBB.
+    pub fn pack_u32(&self) -> u32 {
         let intensity = Interval::new(0.0, 0.999);
-        let r = (255.99 * intensity.clamp(self.x().sqrt())) as u32;
-        let g = (255.99 * intensity.clamp(self.y().sqrt())) as u32;
-        let b = (255.99 * intensity.clamp(self.z().sqrt())) as u32;
+        let r = (255.99 * intensity.clamp(self.x())) as u32;
+        let g = (255.99 * intensity.clamp(self.y())) as u32;
+        let b = (255.99 * intensity.clamp(self.z())) as u32;
         (r << 16) | (g << 8) | b
     }
 
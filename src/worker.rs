@@ -0,0 +1,155 @@
+use std::sync::atomic::{ AtomicBool, AtomicU64, Ordering };
+use std::sync::{ Arc, Condvar, Mutex, MutexGuard };
+use std::thread::{ self, JoinHandle };
+
+use crate::{ Size, background::Background, camera::{ Camera, ViewMode, ViewParams }, scene::Scene };
+
+/// The last completed pass, published for the viewer to display.
+pub struct Framebuffer {
+    pub size: Size,
+    pub pixels: Vec<u32>,
+    pub sample_current: u16,
+}
+
+#[derive(Default)]
+struct Pending {
+    view: Option<ViewParams>,
+    display: Option<(ViewMode, bool)>,
+    background: Option<Background>,
+}
+
+struct Shared {
+    pending: Mutex<Pending>,
+    wake: Condvar,
+    generation: AtomicU64,
+    quit: AtomicBool,
+    frame: Mutex<Framebuffer>,
+}
+
+/// Renders a scene on its own thread so the window loop never waits for a pass.
+pub struct RenderWorker {
+    shared: Arc<Shared>,
+    handle: JoinHandle<Scene>,
+}
+
+impl RenderWorker {
+    /// Whatever `scene.camera` has accumulated so far is shown until the first pass lands.
+    pub fn spawn(scene: Scene) -> Self {
+        let size = scene.camera.image_size;
+        let shared = Arc::new(Shared {
+            pending: Mutex::new(Pending::default()),
+            wake: Condvar::new(),
+            generation: AtomicU64::new(0),
+            quit: AtomicBool::new(false),
+            frame: Mutex::new(Framebuffer {
+                size,
+                pixels: (0..size.area())
+                    .map(|i| scene.camera.pixel_radiance(i).to_u32())
+                    .collect(),
+                sample_current: scene.camera.sample_current,
+            }),
+        });
+        let worker_shared = Arc::clone(&shared);
+        let handle = thread::spawn(move || run(scene, &worker_shared));
+        RenderWorker { shared, handle }
+    }
+
+    /// Moves the camera or changes its lens, cancelling the pass in flight.
+    pub fn set_view(&self, params: ViewParams) {
+        let mut pending = self.shared.pending.lock().unwrap();
+        pending.view = Some(params);
+        self.shared.generation.fetch_add(1, Ordering::SeqCst);
+        self.shared.wake.notify_one();
+    }
+
+    /// Replaces the background, such as after moving the sun, and starts the image over.
+    pub fn set_background(&self, background: Background) {
+        let mut pending = self.shared.pending.lock().unwrap();
+        pending.background = Some(background);
+        self.shared.generation.fetch_add(1, Ordering::SeqCst);
+        self.shared.wake.notify_one();
+    }
+
+    /// Switches the displayed view and denoising without restarting the accumulation.
+    pub fn set_display(&self, view: ViewMode, denoise: bool) {
+        let mut pending = self.shared.pending.lock().unwrap();
+        pending.display = Some((view, denoise));
+        self.shared.wake.notify_one();
+    }
+
+    pub fn frame(&self) -> MutexGuard<'_, Framebuffer> {
+        self.shared.frame.lock().unwrap()
+    }
+
+    /// Cancels the pass in flight and hands the scene back, with the latest camera
+    /// and display settings passed to the worker applied.
+    pub fn stop(self) -> Scene {
+        {
+            let _pending = self.shared.pending.lock().unwrap();
+            self.shared.quit.store(true, Ordering::SeqCst);
+            self.shared.wake.notify_one();
+        }
+        self.handle.join().expect("render worker panicked")
+    }
+}
+
+fn run(mut scene: Scene, shared: &Shared) -> Scene {
+    let mut buffer = Vec::new();
+    loop {
+        let mut display_changed = false;
+        let generation = {
+            let mut pending = shared.pending.lock().unwrap();
+            loop {
+                if let Some(params) = pending.view.take() {
+                    scene.camera.set_view_params(params);
+                }
+                if let Some(background) = pending.background.take() {
+                    scene.background = background;
+                    scene.camera.clear();
+                }
+                if let Some((view, denoise)) = pending.display.take() {
+                    scene.camera.view = view;
+                    scene.camera.denoise = denoise;
+                    display_changed = true;
+                }
+                if shared.quit.load(Ordering::SeqCst) {
+                    return scene;
+                }
+                if display_changed || !scene.camera.is_done() {
+                    break;
+                }
+                pending = shared.wake.wait(pending).unwrap();
+            }
+            shared.generation.load(Ordering::SeqCst)
+        };
+        let cancelled = || {
+            shared.quit.load(Ordering::Relaxed) ||
+                shared.generation.load(Ordering::Relaxed) != generation
+        };
+
+        buffer.resize(scene.camera.image_size.area(), 0);
+        if scene.camera.is_done() {
+            scene.camera.display(&mut buffer);
+            publish(shared, &scene.camera, &buffer);
+            continue;
+        }
+        let completed = scene.camera.render_cancellable(
+            &scene.world,
+            &scene.lights,
+            &scene.background,
+            &mut buffer,
+            &cancelled
+        );
+        if completed && !cancelled() {
+            publish(shared, &scene.camera, &buffer);
+        }
+    }
+}
+
+fn publish(shared: &Shared, camera: &Camera, buffer: &[u32]) {
+    let mut frame = shared.frame.lock().unwrap();
+    frame.size = camera.image_size;
+    frame.pixels.clear();
+    frame.pixels.extend_from_slice(buffer);
+    frame.sample_current = camera.sample_current;
+}